@@ -0,0 +1,244 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt::{self, Display},
+    fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use snap_coin::full_node::node_state::SharedNodeState;
+use tokio::{sync::Mutex, task::JoinHandle, time::sleep};
+
+/// File in the node path that keeps the known peers across restarts
+pub const ADDRESS_BOOK_FILE: &str = "peers.json";
+
+/// How often the address book is updated from the connected peers and saved
+pub const ADDRESS_BOOK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Peers that were not connected for this long are evicted
+pub const STALE_AFTER: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+/// Most peers kept, the least recently seen are evicted beyond it
+pub const MAX_ENTRIES: usize = 1000;
+
+/// Most peers of the address book tried on startup, the reconnection watchdog gets to the others if needed
+pub const STARTUP_CANDIDATES: usize = 16;
+
+pub type SharedAddressBook = Arc<Mutex<AddressBook>>;
+
+/// Where a peer address came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PeerSource {
+    /// A configured seed peer
+    Seed,
+    /// Found by auto peering
+    Discovered,
+}
+
+impl PeerSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            PeerSource::Seed => "seed",
+            PeerSource::Discovered => "discovered",
+        }
+    }
+}
+
+impl Display for PeerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What is known about a peer address
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerEntry {
+    pub address: SocketAddr,
+    pub source: PeerSource,
+    /// Unix time the peer was added
+    pub added: u64,
+    /// Unix time the peer was last connected, `None` if it never was
+    pub last_seen: Option<u64>,
+    pub successes: u32,
+    pub failures: u32,
+}
+
+impl PeerEntry {
+    /// Unix time of the last sign of life, when it was added for peers that were never connected
+    fn last_alive(&self) -> u64 {
+        self.last_seen.unwrap_or(self.added)
+    }
+}
+
+/// Outgoing peers this node knows about, saved in the node path so a restart does not depend on the seed peers alone
+#[derive(Debug)]
+pub struct AddressBook {
+    path: PathBuf,
+    entries: HashMap<SocketAddr, PeerEntry>,
+}
+
+impl AddressBook {
+    /// An empty address book of `node_path`
+    pub fn empty(node_path: &Path) -> AddressBook {
+        AddressBook {
+            path: node_path.join(ADDRESS_BOOK_FILE),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Read the address book saved in `node_path`, empty if there is none yet
+    pub fn load(node_path: &Path) -> Result<AddressBook, anyhow::Error> {
+        let mut book = AddressBook::empty(node_path);
+        let buffer = match fs::read(&book.path) {
+            Ok(buffer) => buffer,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(book),
+            Err(e) => return Err(anyhow!("Failed to read {}: {e}", book.path.display())),
+        };
+        let entries: Vec<PeerEntry> = serde_json::from_slice(&buffer)
+            .map_err(|e| anyhow!("Invalid address book {}: {e}", book.path.display()))?;
+        book.entries = entries
+            .into_iter()
+            .map(|entry| (entry.address, entry))
+            .collect();
+        Ok(book)
+    }
+
+    pub fn into_shared(self) -> SharedAddressBook {
+        Arc::new(Mutex::new(self))
+    }
+
+    /// Write the address book into the node path
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let mut entries = self.entries.values().collect::<Vec<_>>();
+        entries.sort_by_key(|entry| entry.address);
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(&entries)?)
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .map_err(|e| anyhow!("Failed to write {}: {e}", self.path.display()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The entry of `address`, added with `source` if it is not known yet
+    pub fn add(&mut self, address: SocketAddr, source: PeerSource, now: u64) -> &mut PeerEntry {
+        self.entries.entry(address).or_insert(PeerEntry {
+            address,
+            source,
+            added: now,
+            last_seen: None,
+            successes: 0,
+            failures: 0,
+        })
+    }
+
+    /// A connection to `address` succeeded
+    pub fn record_success(&mut self, address: SocketAddr, source: PeerSource, now: u64) {
+        let entry = self.add(address, source, now);
+        entry.successes += 1;
+        entry.last_seen = Some(now);
+    }
+
+    /// A connection to `address` failed
+    pub fn record_failure(&mut self, address: SocketAddr, source: PeerSource, now: u64) {
+        self.add(address, source, now).failures += 1;
+    }
+
+    /// `address` is still connected
+    pub fn record_seen(&mut self, address: SocketAddr, source: PeerSource, now: u64) {
+        self.add(address, source, now).last_seen = Some(now);
+    }
+
+    /// Known addresses to connect to, the most recently seen first. Peers that failed more often than they connected
+    /// come last
+    pub fn candidates(&self) -> Vec<SocketAddr> {
+        let mut entries = self.entries.values().collect::<Vec<_>>();
+        entries.sort_by_key(|entry| {
+            (
+                entry.failures > entry.successes,
+                Reverse(entry.last_alive()),
+                entry.address,
+            )
+        });
+        entries.into_iter().map(|entry| entry.address).collect()
+    }
+
+    /// Evict the peers that were not seen within `STALE_AFTER` before `now`, then the least recently seen beyond
+    /// `MAX_ENTRIES`. Returns the amount evicted
+    pub fn evict_stale(&mut self, now: u64) -> usize {
+        let before = self.entries.len();
+        let oldest = now.saturating_sub(STALE_AFTER.as_secs());
+        self.entries.retain(|_, entry| entry.last_alive() >= oldest);
+
+        if self.entries.len() > MAX_ENTRIES {
+            let mut entries = self.entries.values().collect::<Vec<_>>();
+            entries.sort_by_key(|entry| (Reverse(entry.last_alive()), entry.address));
+            let evicted = entries[MAX_ENTRIES..]
+                .iter()
+                .map(|entry| entry.address)
+                .collect::<Vec<_>>();
+            for address in evicted {
+                self.entries.remove(&address);
+            }
+        }
+        before - self.entries.len()
+    }
+}
+
+/// Seconds since the Unix epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Mark the connected outgoing peers as seen. Peers that connected to us are left out, their port is not the one
+/// they listen on
+pub async fn record_connected_peers(
+    address_book: &SharedAddressBook,
+    node_state: &SharedNodeState,
+) {
+    let outbound = node_state
+        .connected_peers
+        .read()
+        .await
+        .values()
+        .filter(|peer| !peer.is_client)
+        .map(|peer| peer.address)
+        .collect::<Vec<_>>();
+    let now = unix_now();
+    let mut address_book = address_book.lock().await;
+    for address in outbound {
+        address_book.record_seen(address, PeerSource::Discovered, now);
+    }
+}
+
+/// Every `interval`, record the connected peers, evict stale ones and save the address book
+pub fn start_address_book(
+    interval: Duration,
+    address_book: SharedAddressBook,
+    node_state: SharedNodeState,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            sleep(interval).await;
+            record_connected_peers(&address_book, &node_state).await;
+            let mut address_book = address_book.lock().await;
+            let evicted = address_book.evict_stale(unix_now());
+            if evicted > 0 {
+                info!("Evicted {evicted} stale peer(s) from the address book");
+            }
+            if let Err(e) = address_book.save() {
+                warn!("Failed to save the address book: {e}");
+            }
+        }
+    })
+}
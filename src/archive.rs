@@ -0,0 +1,178 @@
+//! Portable block archives, used to seed nodes offline.
+//!
+//! Layout, all integers little endian:
+//! - header: magic `SNAPBLKS`, format version (`u16`), height of the first block (`u64`), amount of blocks (`u64`)
+//! - one record per block: encoded length (`u32`), followed by the bincode (standard config) encoded block
+//! - trailer: SHA-256 of everything before it
+
+use std::io::{self, Read, Write};
+
+use anyhow::anyhow;
+use sha2::{Digest, Sha256};
+use snap_coin::core::block::Block;
+
+pub const ARCHIVE_MAGIC: [u8; 8] = *b"SNAPBLKS";
+
+/// Current archive format version
+pub const ARCHIVE_VERSION: u16 = 1;
+
+/// Largest block record accepted, guards against allocating garbage lengths
+const MAX_RECORD_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArchiveHeader {
+    pub version: u16,
+    /// Height of the first block in the archive
+    pub start_height: usize,
+    /// Amount of blocks in the archive
+    pub block_count: usize,
+}
+
+/// Reads blocks from an archive one at a time, verifying the checksum after the last one
+pub struct ArchiveReader<R: Read> {
+    reader: R,
+    hasher: Sha256,
+    header: ArchiveHeader,
+    read: usize,
+}
+
+impl<R: Read> ArchiveReader<R> {
+    /// Read and check the archive header
+    pub fn open(mut reader: R) -> Result<ArchiveReader<R>, anyhow::Error> {
+        let mut hasher = Sha256::new();
+        let mut header = [0u8; 8 + 2 + 8 + 8];
+        reader
+            .read_exact(&mut header)
+            .map_err(|e| anyhow!("Failed to read archive header: {e}"))?;
+        hasher.update(header);
+
+        if header[..8] != ARCHIVE_MAGIC {
+            return Err(anyhow!("Not a block archive"));
+        }
+        let version = u16::from_le_bytes(header[8..10].try_into()?);
+        if version != ARCHIVE_VERSION {
+            return Err(anyhow!(
+                "Unsupported archive version {version}, expected {ARCHIVE_VERSION}"
+            ));
+        }
+
+        Ok(ArchiveReader {
+            reader,
+            hasher,
+            header: ArchiveHeader {
+                version,
+                start_height: u64::from_le_bytes(header[10..18].try_into()?) as usize,
+                block_count: u64::from_le_bytes(header[18..26].try_into()?) as usize,
+            },
+            read: 0,
+        })
+    }
+
+    pub fn header(&self) -> ArchiveHeader {
+        self.header
+    }
+
+    /// Read the next block, `None` once all blocks are read and the checksum matched
+    pub fn next_block(&mut self) -> Result<Option<Block>, anyhow::Error> {
+        if self.read == self.header.block_count {
+            self.verify_checksum()?;
+            return Ok(None);
+        }
+        let height = self.header.start_height + self.read;
+
+        let mut length = [0u8; 4];
+        self.read_exact(&mut length, height)?;
+        let length = u32::from_le_bytes(length) as usize;
+        if length > MAX_RECORD_SIZE {
+            return Err(anyhow!(
+                "Archive record of block {height} is too large ({length} bytes)"
+            ));
+        }
+
+        let mut record = vec![0u8; length];
+        self.read_exact(&mut record, height)?;
+        let (block, _) = bincode::decode_from_slice(&record, bincode::config::standard())
+            .map_err(|e| anyhow!("Failed to decode archived block {height}: {e}"))?;
+
+        self.read += 1;
+        Ok(Some(block))
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8], height: usize) -> Result<(), anyhow::Error> {
+        self.reader.read_exact(buf).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => anyhow!("Archive is truncated at block {height}"),
+            _ => anyhow!("Failed to read archived block {height}: {e}"),
+        })?;
+        self.hasher.update(&buf);
+        Ok(())
+    }
+
+    fn verify_checksum(&mut self) -> Result<(), anyhow::Error> {
+        let mut checksum = [0u8; 32];
+        self.reader
+            .read_exact(&mut checksum)
+            .map_err(|e| anyhow!("Failed to read archive checksum: {e}"))?;
+        if self.hasher.clone().finalize()[..] != checksum {
+            return Err(anyhow!("Archive checksum mismatch, the file is corrupt"));
+        }
+        Ok(())
+    }
+}
+
+/// Writes blocks into an archive, the amount of blocks has to be known up front
+pub struct ArchiveWriter<W: Write> {
+    writer: W,
+    hasher: Sha256,
+    remaining: usize,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    /// Write the archive header, for `block_count` blocks starting at `start_height`
+    pub fn new(
+        mut writer: W,
+        start_height: usize,
+        block_count: usize,
+    ) -> Result<ArchiveWriter<W>, anyhow::Error> {
+        let mut hasher = Sha256::new();
+        let mut header = ARCHIVE_MAGIC.to_vec();
+        header.extend(ARCHIVE_VERSION.to_le_bytes());
+        header.extend((start_height as u64).to_le_bytes());
+        header.extend((block_count as u64).to_le_bytes());
+        writer.write_all(&header)?;
+        hasher.update(&header);
+
+        Ok(ArchiveWriter {
+            writer,
+            hasher,
+            remaining: block_count,
+        })
+    }
+
+    /// Append the next block
+    pub fn write_block(&mut self, block: &Block) -> Result<(), anyhow::Error> {
+        if self.remaining == 0 {
+            return Err(anyhow!("Archive already holds all of its blocks"));
+        }
+        let record = bincode::encode_to_vec(block, bincode::config::standard())?;
+        let length = (record.len() as u32).to_le_bytes();
+        self.writer.write_all(&length)?;
+        self.writer.write_all(&record)?;
+        self.hasher.update(length);
+        self.hasher.update(&record);
+        self.remaining -= 1;
+        Ok(())
+    }
+
+    /// Write the checksum and flush, fails if fewer blocks were written than announced
+    pub fn finish(mut self) -> Result<W, anyhow::Error> {
+        if self.remaining != 0 {
+            return Err(anyhow!(
+                "Archive is missing {} announced block(s)",
+                self.remaining
+            ));
+        }
+        self.writer.write_all(&self.hasher.finalize())?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
@@ -0,0 +1,86 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use anyhow::anyhow;
+use log::info;
+use snap_coin::full_node::SharedBlockchain;
+
+use crate::{archive::ArchiveReader, network::pin_difficulty, progress::SharedSyncProgress};
+
+/// How many applied blocks between progress log lines
+const LOG_INTERVAL: usize = 1000;
+
+/// Apply the blocks of a block archive to the local chain, through the same validation as a network sync.
+/// Blocks the local chain already has are skipped. Returns the amount of blocks applied
+pub async fn bootstrap_from_file(
+    path: &Path,
+    blockchain: &SharedBlockchain,
+    progress: &SharedSyncProgress,
+    fixed_difficulty: bool,
+) -> Result<usize, anyhow::Error> {
+    let file = File::open(path)
+        .map_err(|e| anyhow!("Failed to open bootstrap file {}: {e}", path.display()))?;
+    let mut archive = ArchiveReader::open(BufReader::new(file))?;
+    let header = archive.header();
+    let end_height = header.start_height + header.block_count;
+
+    let local_height = blockchain.block_store().get_height();
+    if header.start_height > local_height {
+        return Err(anyhow!(
+            "Bootstrap file starts at height {}, but the local chain only has {} blocks",
+            header.start_height,
+            local_height
+        ));
+    }
+
+    info!(
+        "[BOOTSTRAP] Applying blocks {}..{} from {}",
+        local_height.max(header.start_height),
+        end_height,
+        path.display()
+    );
+    {
+        let mut progress = progress.write().await;
+        progress.start(local_height);
+        progress.set_target(end_height, None);
+    }
+
+    let result = async {
+        let mut height = header.start_height;
+        let mut applied = 0;
+        while let Some(block) = archive.next_block()? {
+            // Already have it, just make sure the archive is on our chain
+            if height < blockchain.block_store().get_height() {
+                if blockchain.block_store().get_block_hash_by_height(height) != block.meta.hash {
+                    return Err(anyhow!(
+                        "Bootstrap block {height} does not match the local chain"
+                    ));
+                }
+                height += 1;
+                continue;
+            }
+
+            blockchain
+                .add_block(block, true)
+                .map_err(|e| anyhow!("Bootstrap block {height} is invalid: {e}"))?;
+            if fixed_difficulty {
+                pin_difficulty(blockchain);
+            }
+            height += 1;
+            applied += 1;
+            progress.write().await.block_applied(height);
+
+            if applied % LOG_INTERVAL == 0 {
+                info!("[BOOTSTRAP] Applied block {height}/{end_height}");
+            }
+        }
+        Ok(applied)
+    }
+    .await;
+
+    let mut progress = progress.write().await;
+    if let Err(e) = &result {
+        progress.fail(e.to_string());
+    }
+    progress.finish(blockchain.block_store().get_height());
+    result
+}
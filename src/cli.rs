@@ -0,0 +1,284 @@
+use anyhow::anyhow;
+
+use crate::{
+    client::{ClientOptions, PEERS_OPTIONS, RELOAD_OPTIONS, STATUS_OPTIONS},
+    config::{NODE_OPTIONS, NodeArgs},
+    export::{EXPORT_OPTIONS, ExportOptions},
+    verify::{VERIFY_OPTIONS, VerifyOptions},
+};
+
+pub const BINARY_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// A command line option, `--{name}`
+pub struct OptionSpec {
+    pub name: &'static str,
+    /// Placeholder of the value in the help text, `None` for flags that take no value
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+/// Options of a command, set one by one from `--name value` pairs (flags are set to `"true"`)
+pub trait Options {
+    fn set(&mut self, name: &str, value: &str) -> Result<(), anyhow::Error>;
+}
+
+/// What the binary was asked to do
+pub enum Command {
+    Run(NodeArgs),
+    /// `config dump`
+    ConfigDump(NodeArgs),
+    Export(ExportOptions),
+    Verify(VerifyOptions),
+    Status(ClientOptions),
+    Peers(ClientOptions),
+    Reload(ClientOptions),
+    /// Print this help text
+    Help(String),
+    Version,
+}
+
+/// Subcommands, with a short description for the help text
+const COMMANDS: &[(&str, &str, &[OptionSpec])] = &[
+    ("run", "Run the node (default)", NODE_OPTIONS),
+    (
+        "export",
+        "Write blocks of the local chain into a block archive",
+        EXPORT_OPTIONS,
+    ),
+    (
+        "verify",
+        "Revalidate the stored chain without starting the node",
+        VERIFY_OPTIONS,
+    ),
+    (
+        "status",
+        "Show height and sync progress of a running node",
+        STATUS_OPTIONS,
+    ),
+    ("peers", "List the peers of a running node", PEERS_OPTIONS),
+    (
+        "reload",
+        "Make a running node reload its configuration",
+        RELOAD_OPTIONS,
+    ),
+    (
+        "config",
+        "Print the effective configuration of 'run' with 'config dump'",
+        NODE_OPTIONS,
+    ),
+];
+
+pub fn version() -> String {
+    format!("{} {}", BINARY_NAME, env!("CARGO_PKG_VERSION"))
+}
+
+/// Help text of the whole binary
+fn general_help() -> String {
+    let mut help = format!(
+        "{}\n{}\n\nUsage: {BINARY_NAME} [command] [options]\n\nCommands:\n",
+        version(),
+        env!("CARGO_PKG_DESCRIPTION")
+    );
+    for (name, description, _) in COMMANDS {
+        help.push_str(&format!("  {name:<10}{description}\n"));
+    }
+    help.push_str(&format!(
+        "\nRun '{BINARY_NAME} <command> --help' for the options of a command. Without a command, the node is run with the options of 'run'.\n\nGeneral options:\n  {:<30}Print help\n  {:<30}Print version\n",
+        "-h, --help", "-V, --version"
+    ));
+    help
+}
+
+/// Help text of a single command
+fn command_help(command: &str, description: &str, specs: &[OptionSpec]) -> String {
+    let mut help =
+        format!("{description}\n\nUsage: {BINARY_NAME} {command} [options]\n\nOptions:\n");
+    for spec in specs {
+        let flag = match spec.value {
+            Some(value) => format!("--{} <{}>", spec.name, value),
+            None => format!("--{}", spec.name),
+        };
+        help.push_str(&format!("  {flag:<30}{}\n", spec.help));
+    }
+    help.push_str(&format!("  {:<30}Print help\n", "-h, --help"));
+    help
+}
+
+/// Edit distance between two strings, to suggest the option that was probably meant
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(row[j + 1])
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+/// The option that was probably meant by the unknown option `name`
+pub fn closest_option(name: &str, specs: &[OptionSpec]) -> Option<&'static str> {
+    specs
+        .iter()
+        .map(|spec| (edit_distance(name, spec.name), spec.name))
+        .filter(|(distance, _)| *distance <= 2)
+        .min()
+        .map(|(_, name)| name)
+}
+
+/// Parse the options of `command` into `options`. Accepts `--name value` and `--name=value`, and `--flag=true|false` for flags
+fn parse_options(
+    command: &str,
+    args: &[String],
+    specs: &[OptionSpec],
+    options: &mut impl Options,
+) -> Result<(), anyhow::Error> {
+    let hint = format!("Run '{BINARY_NAME} {command} --help' for the available options");
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.strip_prefix("--") else {
+            return Err(anyhow!("Unexpected argument '{arg}'\n{hint}"));
+        };
+        let (name, inline_value) = match flag.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (flag, None),
+        };
+
+        let Some(spec) = specs.iter().find(|spec| spec.name == name) else {
+            let suggestion = closest_option(name, specs)
+                .map(|suggestion| format!(", did you mean '--{suggestion}'?"))
+                .unwrap_or_default();
+            return Err(anyhow!("Unknown option '{arg}'{suggestion}\n{hint}"));
+        };
+
+        let value = match (spec.value, inline_value) {
+            (Some(_), Some(value)) => value,
+            (Some(placeholder), None) => match args.next() {
+                Some(value) => value.clone(),
+                None => {
+                    return Err(anyhow!(
+                        "Option '--{name}' needs a value <{placeholder}>\n{hint}"
+                    ));
+                }
+            },
+            // `--flag=false` turns off a flag set in the config file
+            (None, Some(value)) => value,
+            (None, None) => "true".to_string(),
+        };
+        options
+            .set(name, &value)
+            .map_err(|e| anyhow!("{e}\n{hint}"))?;
+    }
+    Ok(())
+}
+
+/// Parse the command line arguments (without the binary name)
+pub fn parse_args(args: &[String]) -> Result<Command, anyhow::Error> {
+    if args.iter().any(|arg| arg == "--version" || arg == "-V") {
+        return Ok(Command::Version);
+    }
+
+    // Without a subcommand, the arguments are options of `run`, as before subcommands existed
+    let (command, args, explicit) = match args.first() {
+        Some(first) if !first.starts_with('-') => (first.as_str(), &args[1..], true),
+        _ => ("run", args, false),
+    };
+    let Some((command, description, specs)) = COMMANDS.iter().find(|(name, ..)| *name == command)
+    else {
+        return Err(anyhow!(
+            "Unknown command '{command}'\nRun '{BINARY_NAME} --help' for the available commands"
+        ));
+    };
+
+    // `config` only has the `dump` action
+    let (command, args) = match *command {
+        "config" => match args.first() {
+            Some(action) if action == "dump" => ("config dump", &args[1..]),
+            Some(action) if !action.starts_with('-') => {
+                return Err(anyhow!("Unknown config action '{action}', expected 'dump'"));
+            }
+            _ => ("config dump", args),
+        },
+        command => (command, args),
+    };
+
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        return Ok(Command::Help(if explicit {
+            command_help(command, description, specs)
+        } else {
+            general_help()
+        }));
+    }
+
+    Ok(match command {
+        "run" => {
+            let mut options = NodeArgs::default();
+            parse_options(command, args, specs, &mut options)?;
+            Command::Run(options)
+        }
+        "config dump" => {
+            let mut options = NodeArgs::default();
+            parse_options(command, args, specs, &mut options)?;
+            Command::ConfigDump(options)
+        }
+        "export" => {
+            let mut options = ExportOptions::default();
+            parse_options(command, args, specs, &mut options)?;
+            Command::Export(options)
+        }
+        "verify" => {
+            let mut options = VerifyOptions::default();
+            parse_options(command, args, specs, &mut options)?;
+            Command::Verify(options)
+        }
+        "status" => {
+            let mut options = ClientOptions::default();
+            parse_options(command, args, specs, &mut options)?;
+            Command::Status(options)
+        }
+        "peers" => {
+            let mut options = ClientOptions::default();
+            parse_options(command, args, specs, &mut options)?;
+            Command::Peers(options)
+        }
+        _ => {
+            let mut options = ClientOptions::default();
+            parse_options(command, args, specs, &mut options)?;
+            Command::Reload(options)
+        }
+    })
+}
+
+/// Parse a single option value, with an error naming the option and what it expects
+pub fn parse_value<T: std::str::FromStr>(
+    name: &str,
+    value: &str,
+    expected: &str,
+) -> Result<T, anyhow::Error> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("Invalid value '{value}' for '--{name}', expected {expected}"))
+}
+
+/// Parse a flag value, `true` or `false`
+pub fn parse_bool(name: &str, value: &str) -> Result<bool, anyhow::Error> {
+    parse_value(name, value, "true or false")
+}
+
+/// Parse a comma separated list, ignoring empty entries
+pub fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
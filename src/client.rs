@@ -0,0 +1,200 @@
+use std::net::SocketAddr;
+
+use anyhow::anyhow;
+use snap_coin::{api::client::Client, blockchain_data_provider::BlockchainDataProvider};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::{
+    cli::{OptionSpec, Options, parse_value},
+    network::Network,
+    progress::SyncProgress,
+};
+
+/// Options of `snap-coin-node status`
+pub const STATUS_OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        name: "network",
+        value: Some("name"),
+        help: "Network of the node, selects the default ports [default: mainnet]",
+    },
+    OptionSpec {
+        name: "api-port",
+        value: Some("port"),
+        help: "API port of the running node [default: 3003 on mainnet]",
+    },
+    OptionSpec {
+        name: "status-port",
+        value: Some("port"),
+        help: "Status port of the running node [default: 3004 on mainnet]",
+    },
+];
+
+/// Options of `snap-coin-node peers`
+pub const PEERS_OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        name: "network",
+        value: Some("name"),
+        help: "Network of the node, selects the default port [default: mainnet]",
+    },
+    OptionSpec {
+        name: "api-port",
+        value: Some("port"),
+        help: "API port of the running node [default: 3003 on mainnet]",
+    },
+];
+
+/// Options of `snap-coin-node reload`
+pub const RELOAD_OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        name: "network",
+        value: Some("name"),
+        help: "Network of the node, selects the default port [default: mainnet]",
+    },
+    OptionSpec {
+        name: "status-port",
+        value: Some("port"),
+        help: "Status port of the running node [default: 3004 on mainnet]",
+    },
+];
+
+/// Where to reach a node running on this machine
+#[derive(Clone, Debug, Default)]
+pub struct ClientOptions {
+    pub network: Network,
+    /// `None` for the default ports of `network`
+    pub api_port: Option<u16>,
+    pub status_port: Option<u16>,
+}
+
+impl ClientOptions {
+    pub fn api_port(&self) -> u16 {
+        self.api_port.unwrap_or(self.network.default_api_port())
+    }
+
+    pub fn status_port(&self) -> u16 {
+        self.status_port
+            .unwrap_or(self.network.default_status_port())
+    }
+}
+
+impl Options for ClientOptions {
+    fn set(&mut self, name: &str, value: &str) -> Result<(), anyhow::Error> {
+        match name {
+            "network" => self.network = parse_value(name, value, "mainnet, testnet or regtest")?,
+            "api-port" => self.api_port = Some(parse_value(name, value, "a port number")?),
+            "status-port" => self.status_port = Some(parse_value(name, value, "a port number")?),
+            _ => return Err(anyhow!("Unknown option '--{name}'")),
+        }
+        Ok(())
+    }
+}
+
+/// Connect to the API server of the local node
+async fn connect_api(port: u16) -> Result<Client, anyhow::Error> {
+    Client::connect(SocketAddr::from(([127, 0, 0, 1], port)))
+        .await
+        .map_err(|e| {
+            anyhow!("Failed to reach the node API on port {port}: {e}. Is the node running?")
+        })
+}
+
+/// Send a request to the status server of the local node, returns the status line and the body of the response
+async fn status_request(
+    port: u16,
+    method: &str,
+    path: &str,
+) -> Result<(String, String), anyhow::Error> {
+    let mut stream = TcpStream::connect(SocketAddr::from(([127, 0, 0, 1], port)))
+        .await
+        .map_err(|e| anyhow!("Failed to reach the status server on port {port}: {e}"))?;
+    stream
+        .write_all(
+            format!(
+                "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )
+            .as_bytes(),
+        )
+        .await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| anyhow!("Malformed status server response"))?;
+    Ok((
+        head.lines().next().unwrap_or_default().to_string(),
+        body.to_string(),
+    ))
+}
+
+/// Fetch the sync progress from the status server of the local node
+async fn fetch_sync_progress(port: u16) -> Result<SyncProgress, anyhow::Error> {
+    let (status, body) = status_request(port, "GET", "/sync").await?;
+    if !status.starts_with("HTTP/1.1 200") {
+        return Err(anyhow!("Status server answered {status}"));
+    }
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Entry point of `snap-coin-node status`, prints height, peer count and sync progress of the node running on this machine
+pub async fn run_status(options: ClientOptions) -> Result<(), anyhow::Error> {
+    let client = connect_api(options.api_port()).await?;
+    let height = client.get_height().await?;
+    let peers = client.get_peers().await?;
+    println!("Height: {height}");
+    println!("Peers: {}", peers.len());
+
+    let progress = fetch_sync_progress(options.status_port()).await?;
+    if progress.active {
+        println!(
+            "Syncing: {}/{}, {:.1} blocks/s{}",
+            progress.applied_height,
+            progress.target_height,
+            progress.blocks_per_sec,
+            progress
+                .eta_secs
+                .map(|eta| format!(", ETA {eta}s"))
+                .unwrap_or_default()
+        );
+        if let Some(peer) = progress.source_peer {
+            println!("Sync source: {peer}");
+        }
+    } else {
+        println!("Syncing: no");
+    }
+    if let Some(error) = progress.last_error {
+        println!("Last sync error: {error}");
+    }
+    Ok(())
+}
+
+/// Entry point of `snap-coin-node peers`, lists the peers of the node running on this machine
+pub async fn run_peers(options: ClientOptions) -> Result<(), anyhow::Error> {
+    let client = connect_api(options.api_port()).await?;
+    let peers = client.get_peers().await?;
+    if peers.is_empty() {
+        println!("No connected peers");
+    }
+    for peer in peers {
+        println!("{peer}");
+    }
+    Ok(())
+}
+
+/// Entry point of `snap-coin-node reload`, makes the node running on this machine reload its configuration
+pub async fn run_reload(options: ClientOptions) -> Result<(), anyhow::Error> {
+    let (status, body) = status_request(options.status_port(), "POST", "/reload").await?;
+    let response: serde_json::Value =
+        serde_json::from_str(&body).map_err(|_| anyhow!("Status server answered {status}"))?;
+    match (response["summary"].as_str(), response["error"].as_str()) {
+        (Some(summary), _) => {
+            println!("{summary}");
+            Ok(())
+        }
+        (None, Some(error)) => Err(anyhow!("Reload failed: {error}")),
+        (None, None) => Err(anyhow!("Status server answered {status}")),
+    }
+}
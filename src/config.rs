@@ -0,0 +1,713 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    net::IpAddr,
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use log::LevelFilter;
+use serde::{Deserialize, Deserializer, de};
+use toml::{Spanned, Value, de::DeTable};
+
+use crate::{
+    cli::{OptionSpec, Options, closest_option, parse_bool, parse_list, parse_value},
+    network::Network,
+    seeds::{DEFAULT_SEED_RESOLVE_INTERVAL, IpPreference},
+    sync::{DEFAULT_CATCH_UP_THRESHOLD, DEFAULT_STALL_TIMEOUT, SyncConfig},
+    watchdog::DEFAULT_MIN_PEERS,
+    window::DEFAULT_MAX_WINDOW,
+};
+
+/// Name of the config file looked up in the node path
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Name of the PID file written to the node path in daemon mode
+pub const PID_FILE_NAME: &str = "node.pid";
+
+/// Prefix of the environment variables that set node options
+pub const ENV_PREFIX: &str = "SNAP_NODE_";
+
+/// Options of `snap-coin-node run`
+pub const NODE_OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        name: "config",
+        value: Some("file"),
+        help: "Config file to load [default: <node-path>/config.toml]",
+    },
+    OptionSpec {
+        name: "network",
+        value: Some("name"),
+        help: "mainnet, testnet or regtest, selects the defaults of the options below [default: mainnet]",
+    },
+    OptionSpec {
+        name: "peers",
+        value: Some("addr,..."),
+        help: "Seed peers to connect to, as host:port",
+    },
+    OptionSpec {
+        name: "seed-ip-preference",
+        value: Some("family"),
+        help: "Seed addresses to use: any, ipv4, ipv6 (preferred first), ipv4-only or ipv6-only [default: any]",
+    },
+    OptionSpec {
+        name: "seed-resolve-interval",
+        value: Some("secs"),
+        help: "Seconds between resolving the seed peers again, 0 to never [default: 300]",
+    },
+    OptionSpec {
+        name: "reserved-ips",
+        value: Some("ip,..."),
+        help: "IPs auto peering never connects to",
+    },
+    OptionSpec {
+        name: "max-peers",
+        value: Some("count"),
+        help: "Most connected peers, peers above it are disconnected [default: no limit]",
+    },
+    OptionSpec {
+        name: "min-peers",
+        value: Some("count"),
+        help: "Least outgoing peers, reconnected to seeds and known peers when below it [default: 4]",
+    },
+    OptionSpec {
+        name: "node-path",
+        value: Some("path"),
+        help: "Directory of the node data [default: ./node-<network>]",
+    },
+    OptionSpec {
+        name: "no-api",
+        value: None,
+        help: "Do not start the API and status servers",
+    },
+    OptionSpec {
+        name: "api-port",
+        value: Some("port"),
+        help: "Port of the API server [default: 3003 on mainnet]",
+    },
+    OptionSpec {
+        name: "status-port",
+        value: Some("port"),
+        help: "Port of the status server [default: 3004 on mainnet]",
+    },
+    OptionSpec {
+        name: "node-port",
+        value: Some("port"),
+        help: "Port of the P2P server [default: 8998 on mainnet]",
+    },
+    OptionSpec {
+        name: "create-genesis",
+        value: None,
+        help: "Create and submit a genesis block",
+    },
+    OptionSpec {
+        name: "headless",
+        value: None,
+        help: "Run without the TUI, logging to stdout",
+    },
+    OptionSpec {
+        name: "daemon",
+        value: None,
+        help: "Run as a service: headless, with a PID file and systemd notifications",
+    },
+    OptionSpec {
+        name: "pid-file",
+        value: Some("file"),
+        help: "Where to write the process ID [default: <node-path>/node.pid with --daemon]",
+    },
+    OptionSpec {
+        name: "no-ibd",
+        value: None,
+        help: "Skip the initial block download",
+    },
+    OptionSpec {
+        name: "no-auto-peer",
+        value: None,
+        help: "Do not look for new peers automatically",
+    },
+    OptionSpec {
+        name: "no-catch-up",
+        value: None,
+        help: "Do not sync when falling behind peers after startup",
+    },
+    OptionSpec {
+        name: "catch-up-threshold",
+        value: Some("blocks"),
+        help: "Blocks behind the best peer before catching up [default: 2]",
+    },
+    OptionSpec {
+        name: "max-sync-window",
+        value: Some("blocks"),
+        help: "Most in-flight block downloads per peer [default: 128]",
+    },
+    OptionSpec {
+        name: "sync-stall-timeout",
+        value: Some("secs"),
+        help: "Seconds without progress before a sync peer is dropped [default: 60]",
+    },
+    OptionSpec {
+        name: "bootstrap-file",
+        value: Some("file"),
+        help: "Block archive to apply before syncing",
+    },
+    OptionSpec {
+        name: "log-level",
+        value: Some("level"),
+        help: "off, error, warn or info [default: info]",
+    },
+    OptionSpec {
+        name: "full-memory",
+        value: None,
+        help: "Use the RandomX full memory mode",
+    },
+    OptionSpec {
+        name: "debug",
+        value: None,
+        help: "Start the tokio console subscriber",
+    },
+];
+
+/// Everything a node is started with
+#[derive(Clone, Debug)]
+pub struct NodeConfig {
+    pub network: Network,
+    pub peers: Vec<String>,
+    pub seed_ip_preference: IpPreference,
+    /// Seconds, 0 to never resolve again
+    pub seed_resolve_interval: u64,
+    pub reserved_ips: Vec<IpAddr>,
+    /// `None` for no limit
+    pub max_peers: Option<usize>,
+    pub min_peers: usize,
+    pub node_path: String,
+    pub no_api: bool,
+    pub api_port: u16,
+    pub status_port: u16,
+    pub node_port: u16,
+    pub create_genesis: bool,
+    pub headless: bool,
+    pub daemon: bool,
+    /// `None` for the default of daemon mode, no PID file otherwise
+    pub pid_file: Option<PathBuf>,
+    pub no_ibd: bool,
+    pub no_auto_peer: bool,
+    pub no_catch_up: bool,
+    pub catch_up_threshold: usize,
+    pub max_sync_window: usize,
+    pub sync_stall_timeout: u64,
+    pub bootstrap_file: Option<PathBuf>,
+    pub log_level: LevelFilter,
+    pub full_memory: bool,
+    pub debug: bool,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        NodeConfig::for_network(Network::default())
+    }
+}
+
+impl NodeConfig {
+    /// Defaults of `network`
+    pub fn for_network(network: Network) -> Self {
+        NodeConfig {
+            network,
+            peers: network
+                .default_seeds()
+                .iter()
+                .map(|seed| seed.to_string())
+                .collect(),
+            seed_ip_preference: IpPreference::default(),
+            seed_resolve_interval: DEFAULT_SEED_RESOLVE_INTERVAL.as_secs(),
+            reserved_ips: vec![],
+            max_peers: None,
+            min_peers: DEFAULT_MIN_PEERS,
+            node_path: network.default_node_path().to_string(),
+            no_api: false,
+            api_port: network.default_api_port(),
+            status_port: network.default_status_port(),
+            node_port: network.default_node_port(),
+            create_genesis: false,
+            headless: false,
+            daemon: false,
+            pid_file: None,
+            no_ibd: false,
+            no_auto_peer: false,
+            no_catch_up: false,
+            catch_up_threshold: DEFAULT_CATCH_UP_THRESHOLD,
+            max_sync_window: DEFAULT_MAX_WINDOW,
+            sync_stall_timeout: DEFAULT_STALL_TIMEOUT.as_secs(),
+            bootstrap_file: None,
+            log_level: LevelFilter::Info,
+            full_memory: false,
+            debug: false,
+        }
+    }
+
+    /// PID file to write, `--pid-file` or the default of daemon mode
+    pub fn pid_file(&self) -> Option<PathBuf> {
+        self.pid_file.clone().or_else(|| {
+            self.daemon
+                .then(|| Path::new(&self.node_path).join(PID_FILE_NAME))
+        })
+    }
+
+    /// Sync tuning derived from this config
+    pub fn sync_config(&self) -> SyncConfig {
+        SyncConfig {
+            max_window: self.max_sync_window,
+            stall_timeout: Duration::from_secs(self.sync_stall_timeout),
+            fixed_difficulty: self.network.fixed_difficulty(),
+            ..SyncConfig::default()
+        }
+    }
+
+    /// Apply the settings of a config file on top of this config
+    pub fn apply_file(&mut self, path: &Path) -> Result<(), anyhow::Error> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read config file {}: {e}", path.display()))?;
+        self.apply_toml(&text)
+            .map_err(|e| anyhow!("Invalid config file {}: {e}", path.display()))
+    }
+
+    /// Apply the settings of a config file's contents on top of this config. Keys are the option names, `_` may be
+    /// used instead of `-`. Errors name the line they are on
+    pub fn apply_toml(&mut self, text: &str) -> Result<(), anyhow::Error> {
+        let line = |offset: usize| text[..offset].matches('\n').count() + 1;
+        let error = |e: toml::de::Error, setting: Option<&str>| {
+            let message = e.message().trim_end();
+            match (e.span(), setting) {
+                (Some(span), Some(setting)) => {
+                    anyhow!("line {}: '{setting}': {message}", line(span.start))
+                }
+                (Some(span), None) => anyhow!("line {}: {message}", line(span.start)),
+                (None, _) => anyhow!("{message}"),
+            }
+        };
+
+        let document = DeTable::parse(text).map_err(|e| error(e, None))?;
+        let span = document.span();
+        let mut table = DeTable::new();
+        for (key, value) in document.into_inner() {
+            let key_span = key.span();
+            let name = key.into_inner().replace('_', "-");
+            if table
+                .insert(Spanned::new(key_span.clone(), name.clone().into()), value)
+                .is_some()
+            {
+                return Err(anyhow!(
+                    "line {}: '{name}' is set more than once",
+                    line(key_span.start)
+                ));
+            }
+        }
+
+        // Errors about a value do not say which setting it belongs to, that is looked up by its position
+        let values = table
+            .iter()
+            .map(|(key, value)| (key.get_ref().to_string(), value.span()))
+            .collect::<Vec<_>>();
+        let file = ConfigFile::deserialize(toml::Deserializer::from(Spanned::new(span, table)))
+            .map_err(|e| {
+                let setting = e.span().and_then(|span| {
+                    values
+                        .iter()
+                        .find(|(_, value)| value.contains(&span.start))
+                        .map(|(name, _)| name.as_str())
+                });
+                error(e, setting)
+            })?;
+        file.apply(self);
+        Ok(())
+    }
+
+    /// Every setting with its current value, in the order of `NODE_OPTIONS`. Unset optional settings are `None`
+    pub fn values(&self) -> Vec<(&'static str, Option<Value>)> {
+        let strings =
+            |values: Vec<String>| Value::Array(values.into_iter().map(Value::String).collect());
+        vec![
+            ("network", Some(Value::String(self.network.to_string()))),
+            ("peers", Some(strings(self.peers.clone()))),
+            (
+                "seed-ip-preference",
+                Some(Value::String(self.seed_ip_preference.to_string())),
+            ),
+            (
+                "seed-resolve-interval",
+                Some(Value::Integer(self.seed_resolve_interval as i64)),
+            ),
+            (
+                "reserved-ips",
+                Some(strings(
+                    self.reserved_ips.iter().map(|ip| ip.to_string()).collect(),
+                )),
+            ),
+            (
+                "max-peers",
+                self.max_peers
+                    .map(|max_peers| Value::Integer(max_peers as i64)),
+            ),
+            ("min-peers", Some(Value::Integer(self.min_peers as i64))),
+            ("node-path", Some(Value::String(self.node_path.clone()))),
+            ("no-api", Some(Value::Boolean(self.no_api))),
+            ("api-port", Some(Value::Integer(self.api_port.into()))),
+            ("status-port", Some(Value::Integer(self.status_port.into()))),
+            ("node-port", Some(Value::Integer(self.node_port.into()))),
+            ("create-genesis", Some(Value::Boolean(self.create_genesis))),
+            ("headless", Some(Value::Boolean(self.headless))),
+            ("daemon", Some(Value::Boolean(self.daemon))),
+            (
+                "pid-file",
+                self.pid_file
+                    .as_ref()
+                    .map(|path| Value::String(path.display().to_string())),
+            ),
+            ("no-ibd", Some(Value::Boolean(self.no_ibd))),
+            ("no-auto-peer", Some(Value::Boolean(self.no_auto_peer))),
+            ("no-catch-up", Some(Value::Boolean(self.no_catch_up))),
+            (
+                "catch-up-threshold",
+                Some(Value::Integer(self.catch_up_threshold as i64)),
+            ),
+            (
+                "max-sync-window",
+                Some(Value::Integer(self.max_sync_window as i64)),
+            ),
+            (
+                "sync-stall-timeout",
+                Some(Value::Integer(self.sync_stall_timeout as i64)),
+            ),
+            (
+                "bootstrap-file",
+                self.bootstrap_file
+                    .as_ref()
+                    .map(|path| Value::String(path.display().to_string())),
+            ),
+            (
+                "log-level",
+                Some(Value::String(self.log_level.as_str().to_lowercase())),
+            ),
+            ("full-memory", Some(Value::Boolean(self.full_memory))),
+            ("debug", Some(Value::Boolean(self.debug))),
+        ]
+    }
+
+    /// This config as a config file, that loads back into the same config
+    pub fn to_toml(&self) -> String {
+        self.values()
+            .into_iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("{name} = {value}\n"),
+                None => format!("# {name} =\n"),
+            })
+            .collect()
+    }
+}
+
+impl Options for NodeConfig {
+    fn set(&mut self, name: &str, value: &str) -> Result<(), anyhow::Error> {
+        match name {
+            "network" => self.network = parse_value(name, value, "mainnet, testnet or regtest")?,
+            "peers" => self.peers = parse_list(value),
+            "seed-ip-preference" => {
+                self.seed_ip_preference =
+                    parse_value(name, value, "any, ipv4, ipv6, ipv4-only or ipv6-only")?
+            }
+            "seed-resolve-interval" => {
+                self.seed_resolve_interval = parse_value(name, value, "a number of seconds")?
+            }
+            "reserved-ips" => {
+                self.reserved_ips = parse_list(value)
+                    .iter()
+                    .map(|ip| parse_value(name, ip, "an IP address"))
+                    .collect::<Result<_, _>>()?
+            }
+            "max-peers" => self.max_peers = Some(parse_value(name, value, "a number of peers")?),
+            "min-peers" => self.min_peers = parse_value(name, value, "a number of peers")?,
+            "node-path" => self.node_path = value.to_string(),
+            "no-api" => self.no_api = parse_bool(name, value)?,
+            "api-port" => self.api_port = parse_value(name, value, "a port number")?,
+            "status-port" => self.status_port = parse_value(name, value, "a port number")?,
+            "node-port" => self.node_port = parse_value(name, value, "a port number")?,
+            "create-genesis" => self.create_genesis = parse_bool(name, value)?,
+            "headless" => self.headless = parse_bool(name, value)?,
+            "daemon" => self.daemon = parse_bool(name, value)?,
+            "pid-file" => self.pid_file = Some(PathBuf::from(value)),
+            "no-ibd" => self.no_ibd = parse_bool(name, value)?,
+            "no-auto-peer" => self.no_auto_peer = parse_bool(name, value)?,
+            "no-catch-up" => self.no_catch_up = parse_bool(name, value)?,
+            "catch-up-threshold" => {
+                self.catch_up_threshold = parse_value(name, value, "a number of blocks")?
+            }
+            "max-sync-window" => {
+                self.max_sync_window = parse_value(name, value, "a number of blocks")?
+            }
+            "sync-stall-timeout" => {
+                // With no time at all to make progress, every sync would drop every peer
+                self.sync_stall_timeout =
+                    parse_value::<NonZeroU64>(name, value, "a positive number of seconds")?.get()
+            }
+            "bootstrap-file" => self.bootstrap_file = Some(PathBuf::from(value)),
+            "log-level" => self.log_level = parse_log_level(name, value)?,
+            "full-memory" => self.full_memory = parse_bool(name, value)?,
+            "debug" => self.debug = parse_bool(name, value)?,
+            _ => return Err(anyhow!("Unknown option '--{name}'")),
+        }
+        Ok(())
+    }
+}
+
+/// Parse a log level. The node logger never writes below info, so debug and trace are refused
+fn parse_log_level(name: &str, value: &str) -> Result<LevelFilter, anyhow::Error> {
+    let expected = "off, error, warn or info";
+    let level: LevelFilter = parse_value(name, value, expected)?;
+    if level > LevelFilter::Info {
+        return Err(anyhow!(
+            "Invalid value '{value}' for '--{name}', expected {expected}"
+        ));
+    }
+    Ok(level)
+}
+
+/// Settings of a config file, named like the options. Unset settings keep the value they had
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct ConfigFile {
+    network: Option<Network>,
+    peers: Option<Vec<String>>,
+    seed_ip_preference: Option<IpPreference>,
+    seed_resolve_interval: Option<u64>,
+    reserved_ips: Option<Vec<IpAddr>>,
+    max_peers: Option<usize>,
+    min_peers: Option<usize>,
+    node_path: Option<String>,
+    no_api: Option<bool>,
+    api_port: Option<u16>,
+    status_port: Option<u16>,
+    node_port: Option<u16>,
+    create_genesis: Option<bool>,
+    headless: Option<bool>,
+    daemon: Option<bool>,
+    pid_file: Option<PathBuf>,
+    no_ibd: Option<bool>,
+    no_auto_peer: Option<bool>,
+    no_catch_up: Option<bool>,
+    catch_up_threshold: Option<usize>,
+    max_sync_window: Option<usize>,
+    /// With no time at all to make progress, every sync would drop every peer
+    sync_stall_timeout: Option<NonZeroU64>,
+    bootstrap_file: Option<PathBuf>,
+    #[serde(default, deserialize_with = "deserialize_log_level")]
+    log_level: Option<LevelFilter>,
+    full_memory: Option<bool>,
+    debug: Option<bool>,
+}
+
+impl ConfigFile {
+    fn apply(self, config: &mut NodeConfig) {
+        fn set<T>(setting: &mut T, value: Option<T>) {
+            if let Some(value) = value {
+                *setting = value;
+            }
+        }
+        set(&mut config.network, self.network);
+        set(&mut config.peers, self.peers);
+        set(&mut config.seed_ip_preference, self.seed_ip_preference);
+        set(
+            &mut config.seed_resolve_interval,
+            self.seed_resolve_interval,
+        );
+        set(&mut config.reserved_ips, self.reserved_ips);
+        set(&mut config.max_peers, self.max_peers.map(Some));
+        set(&mut config.min_peers, self.min_peers);
+        set(&mut config.node_path, self.node_path);
+        set(&mut config.no_api, self.no_api);
+        set(&mut config.api_port, self.api_port);
+        set(&mut config.status_port, self.status_port);
+        set(&mut config.node_port, self.node_port);
+        set(&mut config.create_genesis, self.create_genesis);
+        set(&mut config.headless, self.headless);
+        set(&mut config.daemon, self.daemon);
+        set(&mut config.pid_file, self.pid_file.map(Some));
+        set(&mut config.no_ibd, self.no_ibd);
+        set(&mut config.no_auto_peer, self.no_auto_peer);
+        set(&mut config.no_catch_up, self.no_catch_up);
+        set(&mut config.catch_up_threshold, self.catch_up_threshold);
+        set(&mut config.max_sync_window, self.max_sync_window);
+        set(
+            &mut config.sync_stall_timeout,
+            self.sync_stall_timeout.map(NonZeroU64::get),
+        );
+        set(&mut config.bootstrap_file, self.bootstrap_file.map(Some));
+        set(&mut config.log_level, self.log_level);
+        set(&mut config.full_memory, self.full_memory);
+        set(&mut config.debug, self.debug);
+    }
+}
+
+fn deserialize_log_level<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<LevelFilter>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse_log_level("log-level", &value)
+        .map(Some)
+        .map_err(de::Error::custom)
+}
+
+/// Options of `run` as given on the command line. They are only turned into a `NodeConfig` once the config file and environment are read, so that they can override them
+#[derive(Clone, Debug, Default)]
+pub struct NodeArgs {
+    /// `--config`
+    pub config_file: Option<PathBuf>,
+    /// Options in the order they were given, already validated
+    pub options: Vec<(String, String)>,
+}
+
+impl Options for NodeArgs {
+    fn set(&mut self, name: &str, value: &str) -> Result<(), anyhow::Error> {
+        if name == "config" {
+            self.config_file = Some(PathBuf::from(value));
+            return Ok(());
+        }
+        // Validate right away, so mistakes are reported as command line errors
+        NodeConfig::default().set(name, value)?;
+        self.options.push((name.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+impl NodeArgs {
+    /// The effective config: defaults, overridden by the config file, overridden by `SNAP_NODE_*` environment variables, overridden by the command line
+    pub fn resolve(&self) -> Result<(NodeConfig, ConfigSources), anyhow::Error> {
+        self.resolve_with_env(env::vars_os())
+    }
+
+    /// `resolve`, with the environment variables in `vars`
+    pub fn resolve_with_env(
+        &self,
+        vars: impl IntoIterator<Item = (OsString, OsString)>,
+    ) -> Result<(NodeConfig, ConfigSources), anyhow::Error> {
+        let env_options = env_options(vars)?;
+        let last_value = |option: &str| {
+            self.options
+                .iter()
+                .rev()
+                .find(|(name, _)| name == option)
+                .map(|(_, value)| value.clone())
+                .or_else(|| {
+                    env_options
+                        .iter()
+                        .find(|(_, name, _)| *name == option)
+                        .map(|(_, _, value)| value.clone())
+                })
+        };
+
+        // The network picks the defaults, including where the config file is looked for
+        let network: Option<Network> = match last_value("network") {
+            Some(network) => Some(parse_value(
+                "network",
+                &network,
+                "mainnet, testnet or regtest",
+            )?),
+            None => None,
+        };
+
+        // `--config`, otherwise `config.toml` in the node path if it exists
+        let config_file = match self.config_file.clone() {
+            Some(config_file) => Some(config_file),
+            None => match last_value("config") {
+                Some(config_file) => Some(PathBuf::from(config_file)),
+                None => {
+                    let node_path = last_value("node-path")
+                        .unwrap_or(network.unwrap_or_default().default_node_path().to_string());
+                    Some(Path::new(&node_path).join(CONFIG_FILE_NAME)).filter(|path| path.exists())
+                }
+            },
+        };
+
+        let mut config = NodeConfig::for_network(network.unwrap_or_default());
+        if let Some(config_file) = &config_file {
+            config.apply_file(config_file)?;
+            // The file may pick the network as well, then its settings go on top of that network's defaults
+            if network.is_none() && config.network != Network::default() {
+                config = NodeConfig::for_network(config.network);
+                config.apply_file(config_file)?;
+            }
+        }
+        for (_, name, value) in env_options.iter().filter(|(_, name, _)| *name != "config") {
+            config.set(name, value)?;
+        }
+        for (name, value) in &self.options {
+            config.set(name, value)?;
+        }
+
+        Ok((
+            config,
+            ConfigSources {
+                config_file,
+                env_vars: env_options.into_iter().map(|(var, _, _)| var).collect(),
+            },
+        ))
+    }
+}
+
+/// Where the effective config came from, besides the defaults and the command line
+#[derive(Clone, Debug, Default)]
+pub struct ConfigSources {
+    pub config_file: Option<PathBuf>,
+    /// `SNAP_NODE_*` variables that were applied
+    pub env_vars: Vec<String>,
+}
+
+/// Environment variable of an option, `api-port` is set by `SNAP_NODE_API_PORT`
+pub fn env_var_name(option: &str) -> String {
+    format!("{ENV_PREFIX}{}", option.to_uppercase().replace('-', "_"))
+}
+
+/// Node options set by the `SNAP_NODE_*` variables in `vars`, as (variable, option, value), sorted by variable
+fn env_options(
+    vars: impl IntoIterator<Item = (OsString, OsString)>,
+) -> Result<Vec<(String, &'static str, String)>, anyhow::Error> {
+    let mut options = vec![];
+    for (var, value) in vars {
+        let Some(var) = var.to_str().filter(|var| var.starts_with(ENV_PREFIX)) else {
+            continue;
+        };
+        let name = var[ENV_PREFIX.len()..].to_lowercase().replace('_', "-");
+        let Some(spec) = NODE_OPTIONS.iter().find(|spec| spec.name == name) else {
+            let suggestion = closest_option(&name, NODE_OPTIONS)
+                .map(|suggestion| format!(", did you mean {}?", env_var_name(suggestion)))
+                .unwrap_or_default();
+            return Err(anyhow!("Unknown environment variable {var}{suggestion}"));
+        };
+        let value = value
+            .into_string()
+            .map_err(|_| anyhow!("Environment variable {var} is not valid UTF-8"))?;
+
+        if spec.name != "config" {
+            NodeConfig::default()
+                .set(spec.name, &value)
+                .map_err(|e| anyhow!("Invalid environment variable {var}: {e}"))?;
+        }
+        options.push((var.to_string(), spec.name, value));
+    }
+    options.sort();
+    Ok(options)
+}
+
+/// Entry point of `snap-coin-node config dump`, prints the effective config of `run` with the same options as a config file
+pub fn run_config_dump(args: NodeArgs) -> Result<(), anyhow::Error> {
+    let (config, sources) = args.resolve()?;
+    match sources.config_file {
+        Some(config_file) => println!("# Config file: {}", config_file.display()),
+        None => println!("# Config file: none"),
+    }
+    if !sources.env_vars.is_empty() {
+        println!("# Environment: {}", sources.env_vars.join(", "));
+    }
+    print!("{}", config.to_toml());
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use log::warn;
+use snap_coin::full_node::{SharedBlockchain, node_state::SharedNodeState};
+use tokio::{task::JoinHandle, time::sleep};
+
+use crate::progress::SharedSyncProgress;
+
+/// How often the status reported to the service manager is refreshed
+const NOTIFY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Holds the process ID in a file for as long as the node runs, removed on drop
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn create(path: &Path) -> Result<PidFile, anyhow::Error> {
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, format!("{}\n", process::id()))
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(|e| anyhow!("Failed to write PID file {}: {e}", path.display()))?;
+        Ok(PidFile {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to remove PID file {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Sends state changes to the service manager, like `sd_notify`. Without a socket, when not started by systemd with
+/// `Type=notify`, nothing is sent
+#[derive(Clone, Debug, Default)]
+pub struct Notifier {
+    socket: Option<OsString>,
+}
+
+impl Notifier {
+    /// The socket systemd passes in `NOTIFY_SOCKET`
+    pub fn from_env() -> Notifier {
+        Notifier::new(env::var_os("NOTIFY_SOCKET"))
+    }
+
+    /// Notify through `socket`, a path or an abstract socket name starting with `@`
+    pub fn new(socket: Option<OsString>) -> Notifier {
+        Notifier { socket }
+    }
+
+    /// Send newline separated `KEY=value` assignments, like `READY=1` or `STATUS=...`
+    pub fn notify(&self, state: &str) {
+        let Some(socket) = &self.socket else {
+            return;
+        };
+        if let Err(e) = send_notification(socket, state) {
+            warn!("Failed to notify the service manager: {e}");
+        }
+    }
+}
+
+#[cfg(unix)]
+fn send_notification(socket: &OsString, state: &str) -> Result<(), io::Error> {
+    use std::os::unix::{ffi::OsStrExt, net::UnixDatagram};
+
+    let sender = UnixDatagram::unbound()?;
+    match socket.as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+            sender.send_to_addr(state.as_bytes(), &SocketAddr::from_abstract_name(name)?)?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "abstract sockets are only supported on Linux",
+            ));
+        }
+        None => {
+            sender.send_to(state.as_bytes(), socket)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_notification(_socket: &OsString, _state: &str) -> Result<(), io::Error> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "service manager notifications are only supported on Unix",
+    ))
+}
+
+/// Report the startup progress to the service manager, then `READY=1` once the initial sync is done. Afterwards keep
+/// the status up to date with the height and peer count. Start it once the servers are listening
+pub fn start_readiness_notifier(
+    notifier: Notifier,
+    blockchain: SharedBlockchain,
+    node_state: SharedNodeState,
+    sync_progress: SharedSyncProgress,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ready = false;
+        let mut last_status = String::new();
+        loop {
+            let syncing = *node_state.is_syncing.read().await;
+            let status = if !ready && syncing {
+                let progress = sync_progress.read().await;
+                if progress.active {
+                    format!(
+                        "Syncing {}/{}",
+                        progress.applied_height, progress.target_height
+                    )
+                } else {
+                    "Waiting for the initial sync".to_string()
+                }
+            } else {
+                format!(
+                    "Height {}, {} peer(s)",
+                    blockchain.block_store().get_height(),
+                    node_state.connected_peers.read().await.len()
+                )
+            };
+
+            if !ready && !syncing {
+                ready = true;
+                notifier.notify(&format!("READY=1\nSTATUS={status}"));
+                last_status = status;
+            } else if status != last_status {
+                notifier.notify(&format!("STATUS={status}"));
+                last_status = status;
+            }
+            sleep(NOTIFY_INTERVAL).await;
+        }
+    })
+}
@@ -0,0 +1,147 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+
+use anyhow::anyhow;
+use snap_coin::core::blockchain::Blockchain;
+
+use crate::{
+    archive::ArchiveWriter,
+    cli::{OptionSpec, Options, parse_value},
+    lock::NodePathLock,
+    network::Network,
+};
+
+/// How many exported blocks between progress lines
+const PROGRESS_INTERVAL: usize = 1000;
+
+/// Write blocks `from..to` of the chain into an archive. Progress goes to stderr, as the archive itself may go to stdout
+pub fn export_blocks(
+    blockchain: &Blockchain,
+    from: usize,
+    to: usize,
+    writer: impl Write,
+) -> Result<usize, anyhow::Error> {
+    let height = blockchain.block_store().get_height();
+    if from > to || to > height {
+        return Err(anyhow!(
+            "Invalid export range {from}..{to}, the chain has {height} blocks"
+        ));
+    }
+
+    let started_at = Instant::now();
+    let mut archive = ArchiveWriter::new(writer, from, to - from)?;
+    for height in from..to {
+        let block = blockchain
+            .block_store()
+            .get_block_by_height(height)
+            .ok_or_else(|| anyhow!("Block {height} is missing from the block store"))?;
+        archive.write_block(&block)?;
+
+        let exported = height + 1 - from;
+        if exported.is_multiple_of(PROGRESS_INTERVAL) {
+            eprintln!("Exported {exported}/{} blocks", to - from);
+        }
+    }
+    archive.finish()?;
+
+    eprintln!(
+        "Exported blocks {from}..{to} in {:.1}s",
+        started_at.elapsed().as_secs_f64()
+    );
+    Ok(to - from)
+}
+
+/// Options of `snap-coin-node export`
+pub const EXPORT_OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        name: "network",
+        value: Some("name"),
+        help: "Network of the node, selects the default node path [default: mainnet]",
+    },
+    OptionSpec {
+        name: "node-path",
+        value: Some("path"),
+        help: "Directory of the node data [default: ./node-<network>]",
+    },
+    OptionSpec {
+        name: "from",
+        value: Some("height"),
+        help: "First block to export [default: 0]",
+    },
+    OptionSpec {
+        name: "to",
+        value: Some("height"),
+        help: "Height to export up to, exclusive [default: chain height]",
+    },
+    OptionSpec {
+        name: "output",
+        value: Some("file"),
+        help: "File to write the archive to, - for stdout",
+    },
+];
+
+#[derive(Clone, Debug, Default)]
+pub struct ExportOptions {
+    pub network: Network,
+    /// `None` for the default node path of `network`
+    pub node_path: Option<String>,
+    pub from: usize,
+    pub to: Option<usize>,
+    pub output: Option<String>,
+}
+
+impl ExportOptions {
+    pub fn node_path(&self) -> &str {
+        self.node_path
+            .as_deref()
+            .unwrap_or(self.network.default_node_path())
+    }
+}
+
+impl Options for ExportOptions {
+    fn set(&mut self, name: &str, value: &str) -> Result<(), anyhow::Error> {
+        match name {
+            "network" => self.network = parse_value(name, value, "mainnet, testnet or regtest")?,
+            "node-path" => self.node_path = Some(value.to_string()),
+            "from" => self.from = parse_value(name, value, "a block height")?,
+            "to" => self.to = Some(parse_value(name, value, "a block height")?),
+            "output" => self.output = Some(value.to_string()),
+            _ => return Err(anyhow!("Unknown option '--{name}'")),
+        }
+        Ok(())
+    }
+}
+
+/// Entry point of `snap-coin-node export`, writes an archive of the local chain to a file, or to stdout with `--output -`
+pub fn run_export(options: ExportOptions) -> Result<(), anyhow::Error> {
+    let node_path = options.node_path();
+    let ExportOptions {
+        from, to, output, ..
+    } = options.clone();
+    let output = output.ok_or_else(|| anyhow!("Missing --output <file>, use - for stdout"))?;
+
+    let blockchain_path = Path::new(&node_path).join("blockchain");
+    if !blockchain_path.exists() {
+        return Err(anyhow!("No blockchain found at {}", node_path));
+    }
+    let _lock = NodePathLock::acquire(Path::new(&node_path))?;
+    let blockchain = Blockchain::new(
+        blockchain_path
+            .to_str()
+            .ok_or_else(|| anyhow!("Invalid node path"))?,
+    );
+    let to = to.unwrap_or(blockchain.block_store().get_height());
+
+    if output == "-" {
+        export_blocks(&blockchain, from, to, io::stdout().lock())?;
+    } else {
+        let file = File::create(&output)
+            .map_err(|e| anyhow!("Failed to create export file {output}: {e}"))?;
+        export_blocks(&blockchain, from, to, BufWriter::new(file))?;
+    }
+    Ok(())
+}
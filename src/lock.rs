@@ -0,0 +1,115 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use fs2::FileExt;
+use log::warn;
+
+/// File in the node path that is locked by the process using it
+pub const LOCK_FILE: &str = "node.lock";
+
+/// Process that holds, or last held, a node path
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockOwner {
+    pub pid: u32,
+    /// Unix time in seconds
+    pub started: u64,
+}
+
+impl LockOwner {
+    fn parse(contents: &str) -> Option<LockOwner> {
+        let mut pid = None;
+        let mut started = None;
+        for line in contents.lines() {
+            match line.split_once('=') {
+                Some(("pid", value)) => pid = value.trim().parse().ok(),
+                Some(("started", value)) => started = value.trim().parse().ok(),
+                _ => {}
+            }
+        }
+        Some(LockOwner {
+            pid: pid?,
+            started: started?,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let age = unix_time().saturating_sub(self.started);
+        format!("process {}, started {age}s ago", self.pid)
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+/// Exclusive use of a node path, so two processes never write the same chain. The OS drops the lock when the process
+/// dies, the owner left in the file by a crash is detected as stale and taken over. Released on drop
+#[derive(Debug)]
+pub struct NodePathLock {
+    file: File,
+    path: PathBuf,
+    /// Owner of a lock that was taken over, it did not shut down cleanly
+    pub stale: Option<LockOwner>,
+}
+
+impl NodePathLock {
+    /// Lock the existing `node_path`. Fails if another process is using it
+    pub fn acquire(node_path: &Path) -> Result<NodePathLock, anyhow::Error> {
+        let path = node_path.join(LOCK_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| anyhow!("Failed to open lock file {}: {e}", path.display()))?;
+
+        let locked = file.try_lock_exclusive().is_ok();
+        let mut contents = String::new();
+        let _ = file.read_to_string(&mut contents);
+        let previous = LockOwner::parse(&contents);
+
+        if !locked {
+            let owner = previous
+                .map(|owner| format!(" ({})", owner.describe()))
+                .unwrap_or_default();
+            return Err(anyhow!(
+                "Node path {} is in use by another node{owner}. Stop it first, or pick another --node-path",
+                node_path.display()
+            ));
+        }
+        let owner = LockOwner {
+            pid: process::id(),
+            started: unix_time(),
+        };
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "pid={}\nstarted={}\n", owner.pid, owner.started)?;
+        file.sync_all()?;
+        Ok(NodePathLock {
+            file,
+            path,
+            stale: previous,
+        })
+    }
+}
+
+impl Drop for NodePathLock {
+    fn drop(&mut self) {
+        // The file stays, removing it would let a process waiting on the old file and one creating a new file both
+        // lock the node path. An empty file marks a clean release
+        if let Err(e) = self.file.set_len(0) {
+            warn!("Failed to clear lock file {}: {e}", self.path.display());
+        }
+        let _ = self.file.unlock();
+    }
+}
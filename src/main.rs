@@ -1,198 +1,445 @@
-use std::{net::IpAddr, time::Duration, vec};
+use std::{fs, path::Path, time::Duration, vec};
 
 use anyhow::anyhow;
 use log::{error, info, warn};
-use tokio::{net::lookup_host, time::sleep};
+use tokio::{
+    sync::{mpsc, watch},
+    time::sleep,
+};
 
 use snap_coin::{
     api::api_server::{self},
     build_block,
     crypto::randomx_use_full_mode,
     economics::DEV_WALLET,
-    full_node::{
-        accept_block, auto_peer::start_auto_peer, connect_peer, create_full_node,
-        p2p_server::start_p2p_server,
-    },
+    full_node::{accept_block, create_full_node, p2p_server::start_p2p_server},
     node::peer::PeerHandle,
 };
 
 use tracing_subscriber::prelude::*;
 
-use crate::{sync::sync_blockchain, tui::run_tui};
+use crate::{
+    address_book::{
+        ADDRESS_BOOK_INTERVAL, AddressBook, PeerSource, STARTUP_CANDIDATES, start_address_book,
+        unix_now,
+    },
+    bootstrap::bootstrap_from_file,
+    cli::{Command, parse_args, version},
+    client::{run_peers, run_reload, run_status},
+    config::{NodeArgs, NodeConfig, run_config_dump},
+    daemon::{Notifier, PidFile, start_readiness_notifier},
+    export::run_export,
+    lock::NodePathLock,
+    mempool::restore_mempool,
+    network::{check_node_path, start_difficulty_pinning},
+    peers::{start_auto_peer_with_settings, start_peer_limit},
+    progress::SyncProgress,
+    reload::{Reloader, RuntimeSettings, start_reloader},
+    seeds::{connect_peers, start_seed_resolver},
+    shutdown::{NodeTasks, ShutdownReason, Signals, shutdown_node},
+    status::start_status_server,
+    sync::{CATCH_UP_INTERVAL, select_sync_peers, start_catch_up_sync, sync_blockchain},
+    tui::run_tui,
+    verify::run_verify,
+    watchdog::{WATCHDOG_INTERVAL, Watchdog, start_reconnect_watchdog},
+};
 
+mod address_book;
+mod archive;
+mod bootstrap;
+mod cli;
+mod client;
+mod config;
+mod daemon;
+mod export;
+mod lock;
+mod mempool;
+mod network;
+mod peers;
+mod progress;
+mod reload;
+mod reorder;
+mod seeds;
+mod shutdown;
+mod status;
 mod sync;
+#[cfg(test)]
+mod tests;
 mod tui;
+mod verify;
+mod watchdog;
+mod window;
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    let args = std::env::args().collect::<Vec<_>>();
-    let mut peers: Vec<String> = vec![];
-    let mut reserved_ips: Vec<String> = vec![];
-    let mut node_path = "./node-mainnet";
-    let mut start_api = true;
-    let mut api_port = 3003;
-    let mut node_port = 8998;
-    let mut create_genesis = false;
-    let mut headless = false;
-    let mut no_ibd = false;
-    let mut no_auto_peer = false;
-    let mut randomx_full_mode = false;
-
-    for arg in args.iter().enumerate() {
-        if arg.1 == "--peers" && args.get(arg.0 + 1).is_some() {
-            peers = args[arg.0 + 1].split(',').map(|s| s.to_string()).collect();
-        }
-        if arg.1 == "--reserved-ips" && args.get(arg.0 + 1).is_some() {
-            reserved_ips = args[arg.0 + 1].split(',').map(|s| s.to_string()).collect();
-        }
-        if arg.1 == "--node-path" && args.get(arg.0 + 1).is_some() {
-            node_path = &args[arg.0 + 1];
-        }
-        if arg.1 == "--no-api" {
-            start_api = false;
-        }
-        if arg.1 == "--no-ibd" {
-            no_ibd = true;
-        }
-        if arg.1 == "--no-auto-peer" {
-            no_auto_peer = true;
-        }
-        if arg.1 == "--headless" {
-            headless = true;
-        }
-        if arg.1 == "--create-genesis" {
-            create_genesis = true;
-        }
-        if arg.1 == "--full-memory" {
-            randomx_full_mode = true;
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let command = parse_args(&args).unwrap_or_else(|e| usage_error(e));
+
+    match command {
+        Command::Run(args) => {
+            let (config, _) = args.resolve().unwrap_or_else(|e| usage_error(e));
+            run_node(args, config).await
         }
-        if arg.1 == "--api-port" && args.get(arg.0 + 1).is_some() {
-            api_port = args[arg.0 + 1].parse().expect("Invalid api port parameter");
+        Command::ConfigDump(args) => {
+            run_config_dump(args).unwrap_or_else(|e| usage_error(e));
+            Ok(())
         }
-        if arg.1 == "--node-port" && args.get(arg.0 + 1).is_some() {
-            node_port = args[arg.0 + 1]
-                .parse()
-                .expect("Invalid node port parameter");
+        Command::Export(options) => run_export(options),
+        Command::Verify(options) => run_verify(options),
+        Command::Status(options) => run_status(options).await,
+        Command::Peers(options) => run_peers(options).await,
+        Command::Reload(options) => run_reload(options).await,
+        Command::Help(help) => {
+            print!("{help}");
+            Ok(())
         }
-        if arg.1 == "--debug" {
-            if tracing_subscriber::registry()
-                .with(console_subscriber::spawn())
-                .try_init()
-                .is_err()
-            {}
+        Command::Version => {
+            println!("{}", version());
+            Ok(())
         }
     }
+}
+
+/// Report invalid arguments or configuration, and exit with the usage error code
+fn usage_error(e: anyhow::Error) -> ! {
+    eprintln!("error: {e}");
+    std::process::exit(2);
+}
+
+/// Start the node and run it until it is asked to stop by a signal or the TUI, then shut it down cleanly
+async fn run_node(args: NodeArgs, config: NodeConfig) -> Result<(), anyhow::Error> {
+    let sync_config = config.sync_config();
+    let running_config = config.clone();
+    let pid_file = config.pid_file();
+    let NodeConfig {
+        network,
+        node_path,
+        no_api,
+        api_port,
+        status_port,
+        node_port,
+        create_genesis,
+        headless,
+        daemon,
+        no_ibd,
+        no_auto_peer,
+        no_catch_up,
+        catch_up_threshold,
+        seed_resolve_interval,
+        bootstrap_file,
+        full_memory,
+        debug,
+        ..
+    } = config;
+
+    // Daemon mode has no terminal to draw the TUI on
+    let headless = headless || daemon;
 
-    if randomx_full_mode {
+    // From here on SIGINT and SIGTERM shut the node down cleanly instead of killing it
+    let mut signals = Signals::listen()?;
+    let mut listeners = NodeTasks::default();
+    let mut tasks = NodeTasks::default();
+
+    if debug {
+        let _ = tracing_subscriber::registry()
+            .with(console_subscriber::spawn())
+            .try_init();
+    }
+
+    if full_memory {
         randomx_use_full_mode();
     }
 
-    let mut resolved_peers = Vec::new();
+    let runtime_settings = RuntimeSettings::from_config(&running_config).await?;
+    let resolved_peers = runtime_settings.seeds.clone();
 
-    for seed in &peers {
-        match lookup_host(seed).await {
-            Ok(addrs) => {
-                if let Some(addr) = addrs.into_iter().next() {
-                    resolved_peers.push(addr);
-                }
-            }
-            Err(_) => return Err(anyhow!("Failed to resolve or parse seed peer: {seed}")),
-        }
+    // Never share the data with another process, and never mix the data of different networks. The network marker
+    // is checked and written under the lock, so a node for another network cannot mark a fresh node path in between
+    fs::create_dir_all(&node_path)
+        .map_err(|e| anyhow!("Failed to create node path {node_path}: {e}"))?;
+    let lock = NodePathLock::acquire(Path::new(&node_path))?;
+    check_node_path(Path::new(&node_path), network)?;
+    let _pid_file = pid_file.map(|path| PidFile::create(&path)).transpose()?;
+
+    // Create a node and connect it's initial peers to it
+    let (blockchain, node_state) = create_full_node(&node_path, !headless);
+    info!("Running on {}", network);
+    if let Some(stale) = &lock.stale {
+        warn!(
+            "Took over the node path lock of {}, which did not shut down cleanly",
+            stale.describe()
+        );
+    }
+    if network.fixed_difficulty() {
+        tasks.add(
+            "difficulty pinning",
+            start_difficulty_pinning(blockchain.clone(), node_state.clone()),
+        );
     }
 
-    let mut parsed_reserved_ips: Vec<IpAddr> = vec![];
-    for reserved_ip in reserved_ips {
-        parsed_reserved_ips.push(reserved_ip.parse().expect("Reserved ip is invalid"));
+    // Peers known from earlier runs, next to the seeds
+    let address_book = AddressBook::load(Path::new(&node_path))
+        .unwrap_or_else(|e| {
+            warn!("{e}, starting with an empty address book");
+            AddressBook::empty(Path::new(&node_path))
+        })
+        .into_shared();
+    let evicted = address_book.lock().await.evict_stale(unix_now());
+    if evicted > 0 {
+        info!("Evicted {evicted} stale peer(s) from the address book");
     }
+    tasks.add(
+        "address book",
+        start_address_book(
+            ADDRESS_BOOK_INTERVAL,
+            address_book.clone(),
+            node_state.clone(),
+        ),
+    );
 
-    // Create a node and connect it's initial peers to it
-    let (blockchain, node_state) = create_full_node(node_path, !headless);
-    for initial_peer in &resolved_peers {
-        connect_peer(*initial_peer, &blockchain, &node_state).await?;
+    // Seeds, seed IP preference, reserved IPs, log level and peer limits can be changed by a reload on SIGHUP or through the status server
+    log::set_max_level(runtime_settings.log_level);
+    let (settings_sender, settings) = watch::channel(runtime_settings);
+    let (reload_requests, reload_receiver) = mpsc::channel(1);
+    tasks.add(
+        "reloader",
+        start_reloader(
+            Reloader::new(
+                args,
+                running_config,
+                settings_sender.clone(),
+                blockchain.clone(),
+                node_state.clone(),
+                address_book.clone(),
+            ),
+            reload_receiver,
+        )?,
+    );
+    tasks.add(
+        "peer limit",
+        start_peer_limit(node_state.clone(), settings.clone()),
+    );
+    if seed_resolve_interval > 0 {
+        tasks.add(
+            "seed resolver",
+            start_seed_resolver(
+                Duration::from_secs(seed_resolve_interval),
+                settings_sender,
+                blockchain.clone(),
+                node_state.clone(),
+                address_book.clone(),
+            ),
+        );
+    }
+    // Every address of the seed hosts is a candidate, tried in shuffled order until enough of them are connected,
+    // followed by the best few peers of the address book. The reconnection watchdog gets to the others if needed
+    let known_peers = address_book
+        .lock()
+        .await
+        .candidates()
+        .into_iter()
+        .filter(|address| !resolved_peers.contains(address))
+        .take(STARTUP_CANDIDATES)
+        .collect::<Vec<_>>();
+    let initial_peers = resolved_peers.len() + known_peers.len();
+    let mut connected = connect_peers(
+        &resolved_peers,
+        PeerSource::Seed,
+        &blockchain,
+        &node_state,
+        &address_book,
+    )
+    .await;
+    connected += connect_peers(
+        &known_peers,
+        PeerSource::Discovered,
+        &blockchain,
+        &node_state,
+        &address_book,
+    )
+    .await;
+    if initial_peers > 0 && connected == 0 {
+        warn!(
+            "Could not connect to any of the {} seed or known peer address(es)",
+            initial_peers
+        );
     }
 
     *node_state.is_syncing.write().await = true;
+    let sync_progress = SyncProgress::new_shared();
 
-    // If no flags against it, start the Snap Coin API server
-    if start_api {
+    // If no flags against it, start the Snap Coin API server, and the status server next to it
+    if !no_api {
         sleep(Duration::from_secs(1)).await;
-        let api_server = api_server::Server::new(api_port, blockchain.clone(), node_state.clone());
+        let api_server =
+            api_server::Server::new(api_port.into(), blockchain.clone(), node_state.clone());
         api_server.listen().await?;
+        listeners.add(
+            "status server",
+            start_status_server(status_port, sync_progress.clone(), reload_requests).await?,
+        );
     }
 
-    // If the --create-genesis flag passed, create and submit a genesis block
-    if create_genesis {
+    // If the --create-genesis flag passed, or the network creates its own, create and submit a genesis block
+    if create_genesis || (network.creates_genesis() && blockchain.block_store().get_height() == 0) {
         let mut genesis = build_block(&*blockchain, &vec![], DEV_WALLET).await?;
         #[allow(deprecated)]
         genesis.compute_pow()?;
         accept_block(&blockchain, &node_state, genesis).await?;
     }
 
-    // If an initial peer was passed, and no flags against it, connect to the first connected peer, and IBD from it
-    if !resolved_peers.is_empty() && !no_ibd {
-        let peer = node_state
-            .connected_peers
-            .read()
-            .await
-            .values()
-            .collect::<Vec<&PeerHandle>>()[0]
-            .clone();
+    // If a bootstrap file was passed, apply it first. Then if an initial peer was passed, and no flags against it, IBD from all connected peers
+    let ibd = initial_peers > 0 && !no_ibd;
+    if bootstrap_file.is_some() || ibd {
         let blockchain = blockchain.clone();
         let node_state = node_state.clone();
-        tokio::spawn(async move {
-            sleep(Duration::from_secs(1)).await;
-            info!(
-                "Blockchain sync status {:?}",
-                sync_blockchain(peer, blockchain).await
-            );
+        let sync_progress = sync_progress.clone();
+        let config = sync_config.clone();
+        let node_path = node_path.clone();
+        let initial_sync = tokio::spawn(async move {
+            // Same as for any other sync, a block accepted in between could land in the middle of a rollback
+            node_state.mempool.clear().await;
+            let processing = node_state.processing.lock().await;
+            if let Some(bootstrap_file) = bootstrap_file {
+                match bootstrap_from_file(
+                    &bootstrap_file,
+                    &blockchain,
+                    &sync_progress,
+                    config.fixed_difficulty,
+                )
+                .await
+                {
+                    Ok(applied) => info!("Bootstrap applied {} block(s)", applied),
+                    Err(e) => error!("Bootstrap failed: {}", e),
+                }
+            }
+            if ibd {
+                sleep(Duration::from_secs(1)).await;
+                let peers = node_state
+                    .connected_peers
+                    .read()
+                    .await
+                    .values()
+                    .cloned()
+                    .collect::<Vec<PeerHandle>>();
+
+                // Only sync from the highest and fastest peers, and only if any of them is ahead of us
+                let local_height = blockchain.block_store().get_height();
+                let best_peers = select_sync_peers(&peers, local_height, &config).await;
+                if best_peers.is_empty() {
+                    info!(
+                        "No connected peer is ahead of local height {}, skipping initial block download",
+                        local_height
+                    );
+                } else {
+                    info!(
+                        "Blockchain sync status {:?}",
+                        sync_blockchain(best_peers, blockchain.clone(), sync_progress, config)
+                            .await
+                    );
+                }
+            }
+            drop(processing);
             *node_state.is_syncing.write().await = false;
+
+            // Only now, so the saved transactions are validated against the synced chain
+            if let Err(e) = restore_mempool(Path::new(&node_path), &blockchain, &node_state).await {
+                warn!("Failed to restore the saved mempool: {e}");
+            }
         });
+        tasks.add("initial sync", initial_sync);
     } else {
         *node_state.is_syncing.write().await = false;
+        if let Err(e) = restore_mempool(Path::new(&node_path), &blockchain, &node_state).await {
+            warn!("Failed to restore the saved mempool: {e}");
+        }
     }
 
-    if resolved_peers.len() != 0 {
-        let resolved_peers = resolved_peers.clone();
+    // Keeps a minimum of outgoing peers, rotating through the seeds and the peers connected before
+    tasks.add(
+        "reconnection watchdog",
+        start_reconnect_watchdog(
+            WATCHDOG_INTERVAL,
+            Watchdog::new(blockchain.clone(), node_state.clone(), address_book.clone()),
+            settings.clone(),
+            sync_progress.clone(),
+            sync_config.clone(),
+        ),
+    );
 
-        // Peer complete disconnection watchdog
-        let blockchain = blockchain.clone();
-        let node_state = node_state.clone();
-        tokio::spawn(async move {
-            loop {
-                sleep(Duration::from_secs(30)).await;
-                if node_state.connected_peers.read().await.len() == 0 {
-                    warn!("All peers disconnected, trying to reconnect to seed peer");
-                    let res = connect_peer(resolved_peers[0], &blockchain, &node_state).await;
-                    match res {
-                        Ok(peer) => {
-                            info!("Reconnection status: OK");
-                            info!(
-                                "Re-sync status: {:?}",
-                                sync_blockchain(peer, blockchain.clone()).await
-                            );
-                        }
-                        Err(e) => {
-                            error!("Reconnection status: {}", e);
-                        }
-                    }
-                }
-            }
-        });
+    if !no_catch_up {
+        // Keeps pulling blocks whenever we fall behind our peers
+        tasks.add(
+            "catch-up sync",
+            start_catch_up_sync(
+                CATCH_UP_INTERVAL,
+                blockchain.clone(),
+                node_state.clone(),
+                sync_progress.clone(),
+                catch_up_threshold,
+                sync_config.clone(),
+            ),
+        );
     }
 
     if !no_auto_peer {
-        // No need to capture this join handle
-        let _ = start_auto_peer(node_state.clone(), blockchain.clone(), parsed_reserved_ips);
+        tasks.add(
+            "auto peer",
+            start_auto_peer_with_settings(node_state.clone(), blockchain.clone(), settings.clone()),
+        );
     }
 
-    let p2p_server_handle =
+    let mut p2p_server_handle =
         start_p2p_server(node_port, blockchain.clone(), node_state.clone()).await?;
 
-    if headless {
-        info!("{:?}", p2p_server_handle.await);
+    // Everything is listening, the service manager learns that the node is ready once the initial sync is done
+    let notifier = Notifier::from_env();
+    tasks.add(
+        "readiness notifier",
+        start_readiness_notifier(
+            notifier.clone(),
+            blockchain.clone(),
+            node_state.clone(),
+            sync_progress.clone(),
+        ),
+    );
+
+    let mut tui_result = Ok(());
+    let reason = if headless {
+        tokio::select! {
+            signal = signals.recv() => ShutdownReason::Signal(signal),
+            _ = &mut p2p_server_handle => ShutdownReason::P2PServerStopped,
+        }
     } else {
-        run_tui(node_state, blockchain, node_port, node_path.to_string()).await?;
-    }
+        tui_result = run_tui(
+            node_state.clone(),
+            blockchain.clone(),
+            sync_progress,
+            node_port,
+            node_path.clone(),
+            signals.clone(),
+        )
+        .await;
+        signals
+            .received()
+            .map_or(ShutdownReason::Quit, ShutdownReason::Signal)
+    };
+    listeners.add("P2P server", p2p_server_handle);
 
+    notifier.notify("STOPPING=1");
+    shutdown_node(
+        reason,
+        listeners,
+        tasks,
+        &blockchain,
+        &node_state,
+        &address_book,
+        Path::new(&node_path),
+    )
+    .await?;
+    tui_result?;
+    if reason == ShutdownReason::P2PServerStopped {
+        return Err(anyhow!("The P2P server stopped unexpectedly"));
+    }
     Ok(())
 }
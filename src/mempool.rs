@@ -0,0 +1,59 @@
+use std::{fs, io, path::Path};
+
+use anyhow::anyhow;
+use log::{info, warn};
+use snap_coin::{
+    core::transaction::Transaction,
+    full_node::{SharedBlockchain, accept_transaction, node_state::SharedNodeState},
+};
+
+/// File in the node path that keeps the pending transactions across restarts
+pub const MEMPOOL_FILE: &str = "mempool.dat";
+
+/// Write the pending transactions into the node path, so they survive a restart. Returns the amount written
+pub async fn save_mempool(
+    node_path: &Path,
+    node_state: &SharedNodeState,
+) -> Result<usize, anyhow::Error> {
+    let transactions = node_state.mempool.get_mempool().await;
+    let path = node_path.join(MEMPOOL_FILE);
+    let tmp_path = path.with_extension("tmp");
+    let buffer = bincode::encode_to_vec(&transactions, bincode::config::standard())?;
+    fs::write(&tmp_path, buffer)
+        .and_then(|_| fs::rename(&tmp_path, &path))
+        .map_err(|e| anyhow!("Failed to write {}: {e}", path.display()))?;
+    Ok(transactions.len())
+}
+
+/// Put the transactions saved by the last shutdown back into the mempool. They are validated again, expired or
+/// since spent ones are dropped. Returns the amount restored
+pub async fn restore_mempool(
+    node_path: &Path,
+    blockchain: &SharedBlockchain,
+    node_state: &SharedNodeState,
+) -> Result<usize, anyhow::Error> {
+    let path = node_path.join(MEMPOOL_FILE);
+    let buffer = match fs::read(&path) {
+        Ok(buffer) => buffer,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(anyhow!("Failed to read {}: {e}", path.display())),
+    };
+    // Whatever happens below, a saved mempool is only restored once
+    fs::remove_file(&path)?;
+
+    let (transactions, _): (Vec<Transaction>, _) =
+        bincode::decode_from_slice(&buffer, bincode::config::standard())
+            .map_err(|e| anyhow!("Invalid saved mempool {}: {e}", path.display()))?;
+    let saved = transactions.len();
+    let mut restored = 0;
+    for transaction in transactions {
+        match accept_transaction(blockchain, node_state, transaction).await {
+            Ok(()) => restored += 1,
+            Err(e) => warn!("Dropping saved transaction: {e}"),
+        }
+    }
+    if saved > 0 {
+        info!("Restored {restored} of {saved} saved mempool transaction(s)");
+    }
+    Ok(restored)
+}
@@ -0,0 +1,192 @@
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use log::warn;
+use serde::Deserialize;
+use snap_coin::{
+    core::{
+        blockchain::Blockchain,
+        difficulty::{STARTING_BLOCK_DIFFICULTY, STARTING_TX_DIFFICULTY},
+    },
+    full_node::{SharedBlockchain, node_state::ChainEvent, node_state::SharedNodeState},
+};
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+
+/// File in the node path that records which network its data belongs to
+pub const NETWORK_FILE: &str = "network";
+
+/// The network a node runs on. Selects the defaults of the data dir, ports, seed peers and genesis behavior
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[default]
+    Mainnet,
+    /// Only a separate data dir and port set, the chain and its rules are the same as on mainnet
+    Testnet,
+    /// A local chain for testing, with an automatically created genesis and a fixed, trivial difficulty
+    Regtest,
+}
+
+impl Network {
+    pub fn name(self) -> &'static str {
+        match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Regtest => "regtest",
+        }
+    }
+
+    /// Default directory of the node data
+    pub fn default_node_path(self) -> &'static str {
+        match self {
+            Network::Mainnet => "./node-mainnet",
+            Network::Testnet => "./node-testnet",
+            Network::Regtest => "./node-regtest",
+        }
+    }
+
+    /// Default P2P port
+    pub fn default_node_port(self) -> u16 {
+        match self {
+            Network::Mainnet => 8998,
+            Network::Testnet => 18998,
+            Network::Regtest => 28998,
+        }
+    }
+
+    /// Default API port
+    pub fn default_api_port(self) -> u16 {
+        match self {
+            Network::Mainnet => 3003,
+            Network::Testnet => 13003,
+            Network::Regtest => 23003,
+        }
+    }
+
+    /// Default status server port
+    pub fn default_status_port(self) -> u16 {
+        match self {
+            Network::Mainnet => 3004,
+            Network::Testnet => 13004,
+            Network::Regtest => 23004,
+        }
+    }
+
+    /// Seed peers used when none are configured. No public seeds are published yet, so they have to be passed with `--peers`
+    pub fn default_seeds(self) -> &'static [&'static str] {
+        match self {
+            Network::Mainnet | Network::Testnet | Network::Regtest => &[],
+        }
+    }
+
+    /// Whether a genesis block is created when the chain is empty, like `--create-genesis`
+    pub fn creates_genesis(self) -> bool {
+        self == Network::Regtest
+    }
+
+    /// Whether the difficulty stays at the starting difficulty, so blocks can be mined instantly
+    pub fn fixed_difficulty(self) -> bool {
+        self == Network::Regtest
+    }
+}
+
+impl Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Network {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(anyhow!("Unknown network '{s}'")),
+        }
+    }
+}
+
+/// Make sure the data in `node_path` belongs to `network`, and mark fresh node paths with it.
+/// Data without a network marker predates network profiles and is mainnet data
+pub fn check_node_path(node_path: &Path, network: Network) -> Result<(), anyhow::Error> {
+    let marker = node_path.join(NETWORK_FILE);
+    let found = match fs::read_to_string(&marker) {
+        Ok(found) => found
+            .trim()
+            .parse::<Network>()
+            .map_err(|e| anyhow!("Invalid network marker {}: {e}", marker.display()))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            if node_path.join("blockchain").exists() {
+                Network::Mainnet
+            } else {
+                fs::create_dir_all(node_path)?;
+                fs::write(&marker, format!("{network}\n"))?;
+                return Ok(());
+            }
+        }
+        Err(e) => return Err(anyhow!("Failed to read {}: {e}", marker.display())),
+    };
+
+    if found != network {
+        return Err(anyhow!(
+            "Node path {} holds {found} data, refusing to use it for {network}. Pick another --node-path",
+            node_path.display()
+        ));
+    }
+    if !marker.exists() {
+        fs::write(&marker, format!("{network}\n"))?;
+    }
+    Ok(())
+}
+
+/// Network of the data in `node_path`, if it is marked
+pub fn node_path_network(node_path: &Path) -> Option<Network> {
+    fs::read_to_string(node_path.join(NETWORK_FILE))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Reset the difficulty to the starting difficulty. Has to follow every applied block on networks with a fixed difficulty
+pub fn pin_difficulty(blockchain: &Blockchain) {
+    let state = blockchain.get_difficulty_manager();
+    *state.block_difficulty.write().unwrap() = STARTING_BLOCK_DIFFICULTY;
+    *state.transaction_difficulty.write().unwrap() = STARTING_TX_DIFFICULTY;
+}
+
+/// Keep the difficulty pinned after blocks accepted from peers and the API. Those are accepted by snap-coin itself, so
+/// the difficulty can only be pinned right after a block, not in the same critical section. A block validated before
+/// that is checked against the adjusted difficulty and rejected, submitters have to wait for the difficulty to be back
+/// at the starting difficulty before building the next block
+pub fn start_difficulty_pinning(
+    blockchain: SharedBlockchain,
+    node_state: SharedNodeState,
+) -> JoinHandle<()> {
+    let mut events = node_state.chain_events.subscribe();
+    pin_difficulty(&blockchain);
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(ChainEvent::Block { .. }) | Err(RecvError::Lagged(_)) => {
+                    // Not behind the processing lock, a block already waiting on it would be validated first. Pinning
+                    // while another block is added is harmless, it pins after that one again
+                    pin_difficulty(&blockchain);
+                }
+                Ok(_) => {}
+                Err(RecvError::Closed) => {
+                    warn!("Chain events closed, no longer pinning the difficulty");
+                    return;
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use log::info;
+use snap_coin::full_node::{
+    SharedBlockchain, auto_peer::start_auto_peer, node_state::SharedNodeState,
+};
+use tokio::{sync::watch, task::JoinHandle, time::sleep};
+
+use crate::reload::RuntimeSettings;
+
+/// How often the peer limit is checked
+const PEER_LIMIT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Aborts the task when dropped, so it goes down together with the task owning it
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Run auto peering, restarted with the new reserved IPs whenever they change
+pub fn start_auto_peer_with_settings(
+    node_state: SharedNodeState,
+    blockchain: SharedBlockchain,
+    mut settings: watch::Receiver<RuntimeSettings>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let reserved_ips = settings.borrow_and_update().reserved_ips.clone();
+            let _auto_peer = AbortOnDrop(start_auto_peer(
+                node_state.clone(),
+                blockchain.clone(),
+                reserved_ips.clone(),
+            ));
+            loop {
+                if settings.changed().await.is_err() {
+                    return;
+                }
+                if settings.borrow_and_update().reserved_ips != reserved_ips {
+                    break;
+                }
+            }
+            info!("Restarting auto peering with the new reserved IPs");
+        }
+    })
+}
+
+/// Keep the connected peers within `max_peers`, disconnecting incoming peers first
+pub fn start_peer_limit(
+    node_state: SharedNodeState,
+    settings: watch::Receiver<RuntimeSettings>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            sleep(PEER_LIMIT_INTERVAL).await;
+            let Some(max_peers) = settings.borrow().max_peers else {
+                continue;
+            };
+
+            let mut peers = node_state
+                .connected_peers
+                .read()
+                .await
+                .values()
+                .cloned()
+                .collect::<Vec<_>>();
+            if peers.len() <= max_peers {
+                continue;
+            }
+            // Outgoing peers were picked by us, keep them over the ones that connected to us
+            peers.sort_by_key(|peer| !peer.is_client);
+            let excess = peers.len() - max_peers;
+            for peer in peers.into_iter().take(excess) {
+                info!(
+                    "Disconnecting {}, above the limit of {max_peers} peers",
+                    peer.address
+                );
+                let _ = peer
+                    .kill(format!("Above the limit of {max_peers} peers"))
+                    .await;
+            }
+        }
+    })
+}
@@ -0,0 +1,121 @@
+use std::{net::SocketAddr, sync::Arc, time::Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+pub type SharedSyncProgress = Arc<RwLock<SyncProgress>>;
+
+/// Progress of the currently running (or last) blockchain sync, shared between the sync task, the TUI and the status server
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SyncProgress {
+    /// Whether a sync is currently running
+    pub active: bool,
+    /// Height the sync is trying to reach
+    pub target_height: usize,
+    /// Local height, updated after every applied block
+    pub applied_height: usize,
+    /// Average block application rate since the sync started
+    pub blocks_per_sec: f64,
+    /// Estimated seconds until `target_height` is reached
+    pub eta_secs: Option<u64>,
+    /// Peer the block hashes are currently taken from
+    pub source_peer: Option<SocketAddr>,
+    /// Last error that interrupted the running (or last) sync, cleared when a new sync starts
+    pub last_error: Option<String>,
+    /// Amount of blocks rolled back by the last reorg
+    pub last_reorg_depth: Option<usize>,
+    /// Blocks downloaded out of order, waiting for their parents
+    pub buffered_blocks: usize,
+    /// Memory taken up by `buffered_blocks`
+    pub buffered_bytes: usize,
+    #[serde(skip)]
+    started: Option<(Instant, usize)>,
+}
+
+impl SyncProgress {
+    pub fn new_shared() -> SharedSyncProgress {
+        Arc::new(RwLock::new(SyncProgress::default()))
+    }
+
+    /// Mark a sync as started at `height`, forgetting the error of an earlier sync
+    pub fn start(&mut self, height: usize) {
+        self.active = true;
+        self.applied_height = height;
+        self.target_height = self.target_height.max(height);
+        self.blocks_per_sec = 0.0;
+        self.eta_secs = None;
+        self.last_error = None;
+        self.started = Some((Instant::now(), height));
+    }
+
+    /// Set a (new) target and source peer, keeps the rate measurement running across resumed rounds
+    pub fn set_target(&mut self, target_height: usize, source_peer: Option<SocketAddr>) {
+        self.target_height = target_height;
+        self.source_peer = source_peer;
+        self.update_rate();
+    }
+
+    /// Record a newly applied block, with the local height after it
+    pub fn block_applied(&mut self, height: usize) {
+        self.applied_height = height;
+        self.target_height = self.target_height.max(height);
+        self.update_rate();
+    }
+
+    /// Record a rollback of `depth` blocks to a common ancestor at `height`
+    pub fn reorg(&mut self, depth: usize, height: usize) {
+        self.last_reorg_depth = Some(depth);
+        self.applied_height = height;
+        if let Some((_, start_height)) = &mut self.started {
+            *start_height = (*start_height).min(height);
+        }
+    }
+
+    /// Record the state of the reorder buffer
+    pub fn set_buffered(&mut self, blocks: usize, bytes: usize) {
+        self.buffered_blocks = blocks;
+        self.buffered_bytes = bytes;
+    }
+
+    /// Record an error that interrupted the sync
+    pub fn fail(&mut self, error: String) {
+        self.last_error = Some(error);
+    }
+
+    /// Mark the sync as stopped
+    pub fn finish(&mut self, height: usize) {
+        self.active = false;
+        self.applied_height = height;
+        self.eta_secs = None;
+        self.started = None;
+    }
+
+    /// Fraction of the sync done, in `0.0..=1.0`
+    pub fn ratio(&self) -> f64 {
+        let Some((_, start_height)) = self.started else {
+            return 1.0;
+        };
+        let total = self.target_height.saturating_sub(start_height);
+        if total == 0 {
+            return 1.0;
+        }
+        (self.applied_height.saturating_sub(start_height) as f64 / total as f64).clamp(0.0, 1.0)
+    }
+
+    fn update_rate(&mut self) {
+        let Some((started_at, start_height)) = self.started else {
+            return;
+        };
+        let elapsed = started_at.elapsed().as_secs_f64();
+        if elapsed <= 0.0 {
+            return;
+        }
+        self.blocks_per_sec = self.applied_height.saturating_sub(start_height) as f64 / elapsed;
+        let remaining = self.target_height.saturating_sub(self.applied_height);
+        self.eta_secs = if self.blocks_per_sec > 0.0 {
+            Some((remaining as f64 / self.blocks_per_sec).ceil() as u64)
+        } else {
+            None
+        };
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+};
+
+use log::{LevelFilter, error, info, warn};
+use snap_coin::full_node::{SharedBlockchain, node_state::SharedNodeState};
+use tokio::{
+    sync::{mpsc, oneshot, watch},
+    task::JoinHandle,
+};
+
+use crate::{
+    address_book::{PeerSource, SharedAddressBook},
+    config::{NodeArgs, NodeConfig},
+    seeds::{IpPreference, connect_peers, resolve_seeds},
+};
+
+/// Options of `run` that a reload applies, all others only take effect after a restart
+pub const RELOADABLE_OPTIONS: &[&str] = &[
+    "peers",
+    "seed-ip-preference",
+    "reserved-ips",
+    "max-peers",
+    "min-peers",
+    "log-level",
+];
+
+/// Settings that can change while the node is running, shared with the tasks that use them
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuntimeSettings {
+    /// `peers` as configured, resolved again from time to time
+    pub seed_hosts: Vec<String>,
+    pub ip_preference: IpPreference,
+    /// Every address `seed_hosts` resolved to, shuffled and ordered by `ip_preference`
+    pub seeds: Vec<SocketAddr>,
+    pub reserved_ips: Vec<IpAddr>,
+    pub max_peers: Option<usize>,
+    pub min_peers: usize,
+    pub log_level: LevelFilter,
+}
+
+impl RuntimeSettings {
+    pub async fn from_config(config: &NodeConfig) -> Result<RuntimeSettings, anyhow::Error> {
+        Ok(RuntimeSettings {
+            seed_hosts: config.peers.clone(),
+            ip_preference: config.seed_ip_preference,
+            seeds: resolve_seeds(&config.peers, config.seed_ip_preference).await?,
+            reserved_ips: config.reserved_ips.clone(),
+            max_peers: config.max_peers,
+            min_peers: config.min_peers,
+            log_level: config.log_level,
+        })
+    }
+}
+
+/// A reload asked for through the status server, answered with the summary of the reload or why it failed
+pub type ReloadRequest = oneshot::Sender<Result<String, String>>;
+
+/// Settings that differ between the config the node runs with and `new`, but cannot be applied by a reload
+pub fn restart_required(running: &NodeConfig, new: &NodeConfig) -> Vec<&'static str> {
+    running
+        .values()
+        .into_iter()
+        .zip(new.values())
+        .filter(|((name, running), (_, new))| running != new && !RELOADABLE_OPTIONS.contains(name))
+        .map(|((name, _), _)| name)
+        .collect()
+}
+
+/// Re-reads the configuration of a running node, from the same command line, environment and config file it was
+/// started with, and applies what can change at runtime
+pub struct Reloader {
+    args: NodeArgs,
+    /// Config the node was started with, settings that need a restart are compared against it
+    running: NodeConfig,
+    settings: watch::Sender<RuntimeSettings>,
+    blockchain: SharedBlockchain,
+    node_state: SharedNodeState,
+    address_book: SharedAddressBook,
+}
+
+impl Reloader {
+    pub fn new(
+        args: NodeArgs,
+        running: NodeConfig,
+        settings: watch::Sender<RuntimeSettings>,
+        blockchain: SharedBlockchain,
+        node_state: SharedNodeState,
+        address_book: SharedAddressBook,
+    ) -> Reloader {
+        Reloader {
+            args,
+            running,
+            settings,
+            blockchain,
+            node_state,
+            address_book,
+        }
+    }
+
+    /// Reload the configuration. Nothing is applied if it is invalid. Returns a summary of what changed
+    pub async fn reload(&self) -> Result<String, anyhow::Error> {
+        let (config, _) = self.args.resolve()?;
+        let new = RuntimeSettings::from_config(&config).await?;
+        let old = self.settings.borrow().clone();
+        let mut applied = vec![];
+
+        if new.log_level != old.log_level {
+            log::set_max_level(new.log_level);
+            applied.push(format!(
+                "log level {}",
+                new.log_level.as_str().to_lowercase()
+            ));
+        }
+        if new.reserved_ips != old.reserved_ips {
+            applied.push("reserved IPs".to_string());
+        }
+        if new.max_peers != old.max_peers {
+            applied.push(match new.max_peers {
+                Some(max_peers) => format!("peer limit {max_peers}"),
+                None => "no peer limit".to_string(),
+            });
+        }
+        if new.min_peers != old.min_peers {
+            applied.push(format!("minimum peers {}", new.min_peers));
+        }
+        // Addresses are shuffled on every resolution, only a different set is a change
+        let added = new
+            .seeds
+            .iter()
+            .filter(|seed| !old.seeds.contains(seed))
+            .copied()
+            .collect::<Vec<_>>();
+        if !added.is_empty() || old.seeds.iter().any(|seed| !new.seeds.contains(seed)) {
+            let connected = connect_peers(
+                &added,
+                PeerSource::Seed,
+                &self.blockchain,
+                &self.node_state,
+                &self.address_book,
+            )
+            .await;
+            applied.push(format!("seed peers ({connected} newly connected)"));
+        }
+        // The auto peer, peer limit and watchdog tasks pick up their settings from here
+        self.settings.send_replace(new);
+
+        let restart = restart_required(&self.running, &config);
+        if !restart.is_empty() {
+            warn!(
+                "Changed settings that only take effect after a restart: {}",
+                restart.join(", ")
+            );
+        }
+
+        let mut summary = if applied.is_empty() {
+            "No runtime settings changed".to_string()
+        } else {
+            format!("Applied {}", applied.join(", "))
+        };
+        if !restart.is_empty() {
+            summary.push_str(&format!(". Needs a restart: {}", restart.join(", ")));
+        }
+        info!("Reloaded the configuration. {summary}");
+        Ok(summary)
+    }
+}
+
+/// Reload the configuration on SIGHUP and on requests from the status server
+pub fn start_reloader(
+    reloader: Reloader,
+    mut requests: mpsc::Receiver<ReloadRequest>,
+) -> Result<JoinHandle<()>, io::Error> {
+    #[cfg(unix)]
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+
+    Ok(tokio::spawn(async move {
+        loop {
+            #[cfg(unix)]
+            let request = tokio::select! {
+                _ = hangup.recv() => None,
+                Some(request) = requests.recv() => Some(request),
+            };
+            #[cfg(not(unix))]
+            let Some(request) = requests.recv().await.map(Some) else {
+                return;
+            };
+
+            if request.is_none() {
+                info!("Received SIGHUP, reloading the configuration");
+            }
+            let result = reloader.reload().await;
+            if let Err(e) = &result {
+                error!("Reload failed, keeping the current settings: {e}");
+            }
+            if let Some(request) = request {
+                let _ = request.send(result.map_err(|e| e.to_string()));
+            }
+        }
+    }))
+}
@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+
+use bincode::enc::write::SizeWriter;
+use snap_coin::{core::block::Block, node::peer::PeerHandle};
+
+/// Default amount of memory downloaded blocks may take up while they wait for their parents
+pub const DEFAULT_MAX_BUFFERED_BYTES: usize = 64 * 1024 * 1024;
+
+/// Encoded size of a block, close to what it takes up in memory
+pub fn block_size(block: &Block) -> usize {
+    let mut writer = SizeWriter::default();
+    // Encoding into a counter cannot fail on I/O, only on a broken block, which is caught when it is applied
+    let _ = bincode::encode_into_writer(block, &mut writer, bincode::config::standard());
+    writer.bytes_written
+}
+
+/// Holds blocks that were downloaded out of order, and hands them out strictly by height.
+/// Once `max_bytes` worth of blocks is waiting, the buffer reports itself as full, so no new downloads are started
+pub struct ReorderBuffer {
+    blocks: BTreeMap<usize, (PeerHandle, Block, usize)>,
+    next_height: usize,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl ReorderBuffer {
+    /// An empty buffer, that hands out blocks starting at `next_height`
+    pub fn new(next_height: usize, max_bytes: usize) -> ReorderBuffer {
+        ReorderBuffer {
+            blocks: BTreeMap::new(),
+            next_height,
+            bytes: 0,
+            max_bytes,
+        }
+    }
+
+    /// Add a downloaded block at `height`, along with the peer it came from
+    pub fn insert(&mut self, height: usize, peer: PeerHandle, block: Block) {
+        if height < self.next_height {
+            return;
+        }
+        let size = block_size(&block);
+        if let Some((_, _, replaced)) = self.blocks.insert(height, (peer, block, size)) {
+            self.bytes -= replaced;
+        }
+        self.bytes += size;
+    }
+
+    /// Take the block at the next height, if it was downloaded already
+    pub fn pop_next(&mut self) -> Option<(PeerHandle, Block)> {
+        let (peer, block, size) = self.blocks.remove(&self.next_height)?;
+        self.bytes -= size;
+        self.next_height += 1;
+        Some((peer, block))
+    }
+
+    /// Height of the next block to hand out
+    pub fn next_height(&self) -> usize {
+        self.next_height
+    }
+
+    /// Whether enough blocks are waiting that no more downloads should be started
+    pub fn is_full(&self) -> bool {
+        self.bytes >= self.max_bytes
+    }
+
+    /// Amount of blocks waiting for their parents
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Memory taken up by the waiting blocks
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    fmt::{self, Display},
+    net::SocketAddr,
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use log::{info, warn};
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+use snap_coin::full_node::{
+    SharedBlockchain, auto_peer::TARGET_PEERS, connect_peer, node_state::SharedNodeState,
+};
+use tokio::{net::lookup_host, sync::watch, task::JoinHandle, time::sleep, time::timeout};
+
+use crate::{
+    address_book::{PeerSource, SharedAddressBook, unix_now},
+    reload::RuntimeSettings,
+};
+
+/// Default seconds between resolving the seed hosts again
+pub const DEFAULT_SEED_RESOLVE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// How long connecting to a single peer address may take
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Which addresses of the seed hosts are used, and which are tried first
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IpPreference {
+    /// Both, in random order
+    #[default]
+    Any,
+    /// Both, IPv4 first
+    Ipv4,
+    /// Both, IPv6 first
+    Ipv6,
+    Ipv4Only,
+    Ipv6Only,
+}
+
+impl IpPreference {
+    pub fn name(self) -> &'static str {
+        match self {
+            IpPreference::Any => "any",
+            IpPreference::Ipv4 => "ipv4",
+            IpPreference::Ipv6 => "ipv6",
+            IpPreference::Ipv4Only => "ipv4-only",
+            IpPreference::Ipv6Only => "ipv6-only",
+        }
+    }
+
+    /// Filter and order `addrs`, keeping their relative order within each address family
+    pub fn apply(self, addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        let (ipv4, ipv6): (Vec<_>, Vec<_>) = addrs.iter().partition(|addr| addr.is_ipv4());
+        match self {
+            IpPreference::Any => addrs,
+            IpPreference::Ipv4 => [ipv4, ipv6].concat(),
+            IpPreference::Ipv6 => [ipv6, ipv4].concat(),
+            IpPreference::Ipv4Only => ipv4,
+            IpPreference::Ipv6Only => ipv6,
+        }
+    }
+}
+
+impl Display for IpPreference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for IpPreference {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "any" => Ok(IpPreference::Any),
+            "ipv4" => Ok(IpPreference::Ipv4),
+            "ipv6" => Ok(IpPreference::Ipv6),
+            "ipv4-only" => Ok(IpPreference::Ipv4Only),
+            "ipv6-only" => Ok(IpPreference::Ipv6Only),
+            _ => Err(anyhow!("Unknown IP preference '{s}'")),
+        }
+    }
+}
+
+/// Resolve seed peers given as host:port into every address they publish, deduplicated, shuffled and ordered by
+/// `preference`. Hosts that fail to resolve are skipped with a warning, it is an error only if none resolves
+pub async fn resolve_seeds(
+    seeds: &[String],
+    preference: IpPreference,
+) -> Result<Vec<SocketAddr>, anyhow::Error> {
+    let mut resolved = Vec::new();
+    let mut failed = Vec::new();
+    for seed in seeds {
+        let Ok(addrs) = lookup_host(seed).await else {
+            warn!("Failed to resolve or parse seed peer {seed}, skipping it");
+            failed.push(seed.as_str());
+            continue;
+        };
+        for addr in addrs {
+            if !resolved.contains(&addr) {
+                resolved.push(addr);
+            }
+        }
+    }
+    // One unreachable host does not make the others useless
+    if resolved.is_empty() && !failed.is_empty() {
+        return Err(anyhow!(
+            "Failed to resolve or parse any seed peer: {}",
+            failed.join(", ")
+        ));
+    }
+    resolved.shuffle(&mut rand::rng());
+    Ok(preference.apply(resolved))
+}
+
+/// Connect to peer addresses in order, until `TARGET_PEERS` outgoing peers are connected. Each attempt is recorded
+/// in the address book, addresses it does not know yet are added with `source`. Returns how many were connected
+pub async fn connect_peers(
+    candidates: &[SocketAddr],
+    source: PeerSource,
+    blockchain: &SharedBlockchain,
+    node_state: &SharedNodeState,
+    address_book: &SharedAddressBook,
+) -> usize {
+    let mut connected = 0;
+    for addr in candidates {
+        {
+            let peers = node_state.connected_peers.read().await;
+            if peers.values().filter(|peer| !peer.is_client).count() >= TARGET_PEERS {
+                break;
+            }
+            if peers.contains_key(addr) {
+                continue;
+            }
+        }
+        let result = timeout(CONNECT_TIMEOUT, connect_peer(*addr, blockchain, node_state)).await;
+        let mut address_book = address_book.lock().await;
+        match result {
+            Ok(Ok(_)) => {
+                info!("Connected to {source} peer {addr}");
+                address_book.record_success(*addr, source, unix_now());
+                connected += 1;
+                continue;
+            }
+            Ok(Err(e)) => warn!("Failed to connect to {source} peer {addr}: {e}"),
+            Err(_) => warn!("Failed to connect to {source} peer {addr}: timed out"),
+        }
+        address_book.record_failure(*addr, source, unix_now());
+    }
+    connected
+}
+
+/// Resolve the seed hosts again every `interval`, so addresses rotated in behind a hostname are picked up. New
+/// addresses are connected to while there are fewer than `TARGET_PEERS` outgoing peers
+pub fn start_seed_resolver(
+    interval: Duration,
+    settings: watch::Sender<RuntimeSettings>,
+    blockchain: SharedBlockchain,
+    node_state: SharedNodeState,
+    address_book: SharedAddressBook,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            sleep(interval).await;
+            let (hosts, preference, known) = {
+                let settings = settings.borrow();
+                (
+                    settings.seed_hosts.clone(),
+                    settings.ip_preference,
+                    settings.seeds.clone(),
+                )
+            };
+            if hosts.is_empty() {
+                continue;
+            }
+
+            let seeds = match resolve_seeds(&hosts, preference).await {
+                Ok(seeds) => seeds,
+                Err(e) => {
+                    warn!("{e}, keeping the known addresses");
+                    continue;
+                }
+            };
+            let added = seeds
+                .iter()
+                .filter(|seed| !known.contains(seed))
+                .copied()
+                .collect::<Vec<_>>();
+            // A reload may have replaced the seed hosts in the meantime
+            let updated = settings.send_if_modified(|settings| {
+                if settings.seed_hosts != hosts || settings.ip_preference != preference {
+                    return false;
+                }
+                settings.seeds = seeds;
+                true
+            });
+            if updated && !added.is_empty() {
+                info!("Seed peers resolved to {} new address(es)", added.len());
+                connect_peers(
+                    &added,
+                    PeerSource::Seed,
+                    &blockchain,
+                    &node_state,
+                    &address_book,
+                )
+                .await;
+            }
+        }
+    })
+}
@@ -0,0 +1,195 @@
+use std::{fmt, io, path::Path, time::Duration};
+
+use anyhow::anyhow;
+use log::{error, info, warn};
+use snap_coin::full_node::{SharedBlockchain, node_state::SharedNodeState};
+use tokio::{sync::watch, task::JoinHandle, time::timeout};
+
+use crate::{
+    address_book::{SharedAddressBook, record_connected_peers},
+    mempool::save_mempool,
+};
+
+/// How long a shutdown may take before it is given up as failed
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Exit code when a second signal cuts the shutdown short, 128 + SIGINT like a shell reports it
+pub const FORCED_EXIT_CODE: i32 = 130;
+
+/// Why the node stops
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShutdownReason {
+    /// SIGINT or SIGTERM
+    Signal(&'static str),
+    /// `q` in the TUI
+    Quit,
+    /// The P2P server stopped accepting connections on its own
+    P2PServerStopped,
+}
+
+impl fmt::Display for ShutdownReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShutdownReason::Signal(signal) => write!(f, "received {signal}"),
+            ShutdownReason::Quit => f.write_str("quit from the TUI"),
+            ShutdownReason::P2PServerStopped => f.write_str("the P2P server stopped"),
+        }
+    }
+}
+
+/// Termination signals of the process. The first one asks for a clean shutdown, a second one exits right away
+#[derive(Clone)]
+pub struct Signals {
+    received: watch::Receiver<Option<&'static str>>,
+}
+
+impl Signals {
+    /// Install the signal handlers. Signals arriving from now on no longer kill the process
+    pub fn listen() -> Result<Signals, io::Error> {
+        let (sender, received) = watch::channel(None);
+
+        #[cfg(unix)]
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        #[cfg(unix)]
+        let mut interrupt =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())?;
+
+        tokio::spawn(async move {
+            loop {
+                #[cfg(unix)]
+                let signal = tokio::select! {
+                    _ = interrupt.recv() => "SIGINT",
+                    _ = terminate.recv() => "SIGTERM",
+                };
+                #[cfg(not(unix))]
+                let signal = match tokio::signal::ctrl_c().await {
+                    Ok(()) => "Ctrl+C",
+                    Err(_) => return,
+                };
+
+                if sender.borrow().is_some() {
+                    eprintln!("Received {signal} again, exiting without a clean shutdown");
+                    std::process::exit(FORCED_EXIT_CODE);
+                }
+                sender.send_replace(Some(signal));
+            }
+        });
+        Ok(Signals { received })
+    }
+
+    /// The signal that asked the node to stop, if any arrived yet
+    pub fn received(&self) -> Option<&'static str> {
+        *self.received.borrow()
+    }
+
+    /// Wait for the first signal
+    pub async fn recv(&mut self) -> &'static str {
+        match self.received.wait_for(Option::is_some).await {
+            Ok(signal) => signal.unwrap(),
+            // The handler task never exits while signals can still arrive
+            Err(_) => std::future::pending().await,
+        }
+    }
+}
+
+/// Background tasks of a running node, stopped in the order they were added
+#[derive(Default)]
+pub struct NodeTasks {
+    tasks: Vec<(&'static str, JoinHandle<()>)>,
+}
+
+impl NodeTasks {
+    pub fn add(&mut self, name: &'static str, handle: JoinHandle<()>) {
+        self.tasks.push((name, handle));
+    }
+
+    /// Cancel all tasks and wait until they are gone. A task that is applying a block finishes that block first,
+    /// since blocks are applied without yielding
+    async fn stop(self) {
+        for (name, handle) in self.tasks {
+            if handle.is_finished() {
+                continue;
+            }
+            handle.abort();
+            if let Err(e) = handle.await
+                && !e.is_cancelled()
+            {
+                warn!("Task {name} failed while stopping: {e}");
+            }
+        }
+    }
+}
+
+/// Stop a running node: stop accepting connections, cancel syncs and other background tasks, let an accepted block
+/// finish, flush the chain state, save the mempool and the address book and disconnect all peers
+pub async fn shutdown_node(
+    reason: ShutdownReason,
+    listeners: NodeTasks,
+    tasks: NodeTasks,
+    blockchain: &SharedBlockchain,
+    node_state: &SharedNodeState,
+    address_book: &SharedAddressBook,
+    node_path: &Path,
+) -> Result<(), anyhow::Error> {
+    info!("Shutting down, {reason}");
+    let result = timeout(SHUTDOWN_TIMEOUT, async {
+        // The API server offers no way to stop it, it keeps answering until the process exits
+        listeners.stop().await;
+        info!("Stopped accepting connections");
+
+        if *node_state.is_syncing.read().await {
+            info!(
+                "Cancelling the running sync at height {}",
+                blockchain.block_store().get_height()
+            );
+        }
+        tasks.stop().await;
+
+        // Blocks from peers and the API are accepted under this lock, wait for the current one
+        let _processing = node_state.processing.lock().await;
+        blockchain.get_utxos().db.flush()?;
+        let saved = save_mempool(node_path, node_state).await?;
+        info!(
+            "Flushed the chain at height {}, saved {saved} mempool transaction(s)",
+            blockchain.block_store().get_height()
+        );
+
+        // The peers connected at the end are the first ones tried on the next start
+        record_connected_peers(address_book, node_state).await;
+        {
+            let address_book = address_book.lock().await;
+            address_book.save()?;
+            info!("Saved {} known peer(s)", address_book.len());
+        }
+
+        let peers = node_state
+            .connected_peers
+            .write()
+            .await
+            .drain()
+            .map(|(_, peer)| peer)
+            .collect::<Vec<_>>();
+        for peer in &peers {
+            if let Err(e) = peer.kill("Node shutting down".to_string()).await {
+                warn!("Failed to disconnect {}: {e}", peer.address);
+            }
+        }
+        info!("Disconnected {} peer(s)", peers.len());
+        Ok::<(), anyhow::Error>(())
+    })
+    .await
+    .unwrap_or_else(|_| {
+        Err(anyhow!(
+            "Shutdown did not finish within {}s",
+            SHUTDOWN_TIMEOUT.as_secs()
+        ))
+    });
+
+    match &result {
+        Ok(()) => info!("Shutdown complete"),
+        Err(e) => error!("Shutdown failed: {e}"),
+    }
+    log::logger().flush();
+    result
+}
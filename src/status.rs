@@ -0,0 +1,138 @@
+use std::{net::SocketAddr, time::Duration};
+
+use anyhow::anyhow;
+use log::{info, warn};
+use tokio::{
+    io::{self, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time::timeout,
+};
+
+use crate::{progress::SharedSyncProgress, reload::ReloadRequest};
+
+/// Most bytes of the request line and headers together
+const MAX_HEAD_BYTES: u64 = 8 * 1024;
+
+/// How long a client may take to send its request line and headers
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long unread request data is drained after responding
+const LINGER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Read the request line and the headers, at most `MAX_HEAD_BYTES` of them. Returns the request line, `None` if the
+/// head is larger
+async fn read_head(stream: &mut BufReader<TcpStream>) -> Result<Option<String>, io::Error> {
+    let mut head = stream.take(MAX_HEAD_BYTES);
+    let mut request_line = None;
+    loop {
+        let mut line = String::new();
+        let read = head.read_line(&mut line).await?;
+        // Cut off by the limit before the end of the head
+        if head.limit() == 0 && !line.ends_with('\n') {
+            return Ok(None);
+        }
+        if read == 0 || (request_line.is_some() && line.trim().is_empty()) {
+            return Ok(Some(request_line.unwrap_or_default()));
+        }
+        // Only the request line is used, the headers are drained
+        request_line.get_or_insert(line);
+    }
+}
+
+/// Serve a single HTTP request
+async fn handle(
+    stream: TcpStream,
+    sync_progress: SharedSyncProgress,
+    reload: mpsc::Sender<ReloadRequest>,
+) -> Result<(), anyhow::Error> {
+    let mut stream = BufReader::new(stream);
+
+    let head = timeout(READ_TIMEOUT, read_head(&mut stream))
+        .await
+        .map_err(|_| anyhow!("No request within {}s", READ_TIMEOUT.as_secs()))??;
+    let Some(request_line) = head else {
+        return respond(
+            &mut stream,
+            "431 Request Header Fields Too Large",
+            "{\"error\":\"request head too large\"}",
+        )
+        .await;
+    };
+    let mut request = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (request.next(), request.next()) else {
+        return respond(
+            &mut stream,
+            "400 Bad Request",
+            "{\"error\":\"bad request\"}",
+        )
+        .await;
+    };
+
+    let (status, body) = match (method, path) {
+        ("GET", "/sync") => (
+            "200 OK",
+            serde_json::to_string(&*sync_progress.read().await)?,
+        ),
+        ("POST", "/reload") => {
+            let (answer, result) = oneshot::channel();
+            reload.send(answer).await?;
+            match result.await? {
+                Ok(summary) => (
+                    "200 OK",
+                    serde_json::json!({ "summary": summary }).to_string(),
+                ),
+                Err(error) => (
+                    "500 Internal Server Error",
+                    serde_json::json!({ "error": error }).to_string(),
+                ),
+            }
+        }
+        _ => ("404 Not Found", "{\"error\":\"not found\"}".to_string()),
+    };
+    respond(&mut stream, status, &body).await
+}
+
+/// Write a JSON response and close the connection
+async fn respond(
+    stream: &mut BufReader<TcpStream>,
+    status: &str,
+    body: &str,
+) -> Result<(), anyhow::Error> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let stream = stream.get_mut();
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    // Closing with unread request data resets the connection, which can discard the response before the client
+    // reads it. Give the client a moment to see it, without reading more than the head limit again
+    let mut rest = stream.take(MAX_HEAD_BYTES);
+    let _ = timeout(LINGER_TIMEOUT, io::copy(&mut rest, &mut io::sink())).await;
+    Ok(())
+}
+
+/// Start a minimal HTTP server on localhost, exposing node status as JSON for dashboards.
+/// `GET /sync` returns the current sync progress, `POST /reload` reloads the configuration
+pub async fn start_status_server(
+    port: u16,
+    sync_progress: SharedSyncProgress,
+    reload: mpsc::Sender<ReloadRequest>,
+) -> Result<JoinHandle<()>, std::io::Error> {
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], port))).await?;
+    info!("Status server listening on {}", listener.local_addr()?);
+
+    Ok(tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let sync_progress = sync_progress.clone();
+            let reload = reload.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle(stream, sync_progress, reload).await {
+                    warn!("Status client error: {e}");
+                }
+            });
+        }
+    }))
+}
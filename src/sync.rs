@@ -1,104 +1,776 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt,
+    net::SocketAddr,
+    pin::pin,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
 use anyhow::anyhow;
-use futures::stream::{FuturesUnordered, StreamExt};
-use log::info;
+use futures::{
+    future::join_all,
+    stream::{self, FuturesUnordered, StreamExt},
+};
+use log::{info, warn};
 use snap_coin::{
-    full_node::SharedBlockchain,
+    core::block::Block,
+    crypto::Hash,
+    full_node::{SharedBlockchain, node_state::SharedNodeState},
     node::{
         message::{Command, Message},
         peer::PeerHandle,
     },
 };
+use tokio::{
+    sync::mpsc,
+    task::JoinHandle,
+    time::{sleep, sleep_until, timeout},
+};
 
-pub async fn sync_blockchain(
+use crate::{
+    network::pin_difficulty,
+    progress::SharedSyncProgress,
+    reorder::{DEFAULT_MAX_BUFFERED_BYTES, ReorderBuffer},
+    window::{DEFAULT_MAX_WINDOW, DownloadWindow},
+};
+
+/// Amount of consecutive blocks handed to one peer, before moving on to the next one
+const CHUNK_SIZE: usize = 16;
+
+/// Default max amount of blocks rolled back when switching forks
+const MAX_REORG_DEPTH: usize = 100;
+
+/// Max amount of peers a sync is started with
+pub const MAX_SYNC_PEERS: usize = 8;
+
+/// Default amount of block hashes requested at once, keeps memory use flat on long chains
+const HASH_WINDOW: usize = 500;
+
+/// How often the catch-up daemon compares the local height with its peers
+pub const CATCH_UP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default amount of blocks the local chain may lag behind the best peer, before the catch-up daemon syncs
+pub const DEFAULT_CATCH_UP_THRESHOLD: usize = 2;
+
+/// Default time without a newly applied block, after which a sync counts as stalled
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Retry and resume behavior of a blockchain sync
+#[derive(Clone, Debug)]
+pub struct SyncConfig {
+    /// Failed attempts on one peer, before a block is re-requested from a different peer
+    pub attempts_per_peer: usize,
+    /// Failed attempts (across all peers) after which a block download is given up on
+    pub max_attempts: usize,
+    /// Wait before the first retry of a block, doubled on every further failure
+    pub retry_backoff: Duration,
+    /// Upper bound for the retry wait
+    pub max_retry_backoff: Duration,
+    /// How long to wait for a peer to answer a single request
+    pub request_timeout: Duration,
+    /// Rounds in a row that may fail without applying a block, before the sync is abandoned
+    pub max_stalled_rounds: usize,
+    /// Amount of block hashes requested at once
+    pub hash_window: usize,
+    /// Max amount of local blocks that may be rolled back to switch to a peer's fork
+    pub max_reorg_depth: usize,
+    /// Upper bound for the adaptive amount of block requests in flight to one peer
+    pub max_window: usize,
+    /// Memory downloaded blocks may take up while they wait to be applied in order, no new blocks are requested past it
+    pub max_buffered_bytes: usize,
+    /// Time without a newly applied block, after which the peer holding things up is dropped and the sync restarted without it
+    pub stall_timeout: Duration,
+    /// Pin the difficulty after every applied block, for networks with a fixed difficulty
+    pub fixed_difficulty: bool,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        SyncConfig {
+            attempts_per_peer: 3,
+            max_attempts: 9,
+            retry_backoff: Duration::from_millis(250),
+            max_retry_backoff: Duration::from_secs(8),
+            request_timeout: Duration::from_secs(10),
+            max_stalled_rounds: 3,
+            hash_window: HASH_WINDOW,
+            max_reorg_depth: MAX_REORG_DEPTH,
+            max_window: DEFAULT_MAX_WINDOW,
+            max_buffered_bytes: DEFAULT_MAX_BUFFERED_BYTES,
+            stall_timeout: DEFAULT_STALL_TIMEOUT,
+            fixed_difficulty: false,
+        }
+    }
+}
+
+impl SyncConfig {
+    /// Wait before retry number `attempt` (starting at 1)
+    fn backoff(&self, attempt: usize) -> Duration {
+        let factor = 1u32 << (attempt.saturating_sub(1)).min(16);
+        self.retry_backoff
+            .saturating_mul(factor)
+            .min(self.max_retry_backoff)
+    }
+}
+
+/// A sync round applied no block for `timeout`, while waiting on block `height` from `peer`
+#[derive(Debug)]
+struct Stalled {
+    peer: SocketAddr,
+    height: usize,
+    timeout: Duration,
+}
+
+impl fmt::Display for Stalled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Sync stalled, no block applied for {:?} while waiting on block {} from {}",
+            self.timeout, self.height, self.peer
+        )
+    }
+}
+
+impl std::error::Error for Stalled {}
+
+/// Ask a peer for its current blockchain height
+async fn peer_height(
+    peer: &PeerHandle,
+    local_height: usize,
+    config: &SyncConfig,
+) -> Result<usize, anyhow::Error> {
+    let response = timeout(
+        config.request_timeout,
+        peer.request(Message::new(Command::Ping {
+            height: local_height,
+        })),
+    )
+    .await
+    .map_err(|_| anyhow!("Timed out waiting for peer height"))??;
+
+    match response.command {
+        Command::Pong { height } => Ok(height),
+        _ => Err(anyhow!("Could not fetch peer height to sync blockchain")),
+    }
+}
+
+/// A peer, with the height it reported and how long it took to report it
+#[derive(Clone, Debug)]
+pub struct RankedPeer {
+    pub peer: PeerHandle,
+    pub height: usize,
+    pub latency: Duration,
+}
+
+/// Ping every peer, and rank the ones that answered by height (highest first), then by latency (fastest first)
+pub async fn rank_peers(
+    peers: &[PeerHandle],
+    local_height: usize,
+    config: &SyncConfig,
+) -> Vec<RankedPeer> {
+    let pings = join_all(peers.iter().map(|peer| async move {
+        let started = Instant::now();
+        let height = peer_height(peer, local_height, config).await;
+        (peer, height, started.elapsed())
+    }))
+    .await;
+
+    let mut ranked = vec![];
+    for (peer, height, latency) in pings {
+        match height {
+            Ok(height) => ranked.push(RankedPeer {
+                peer: peer.clone(),
+                height,
+                latency,
+            }),
+            Err(e) => warn!(
+                "[SYNC] Peer {} did not report its height: {}",
+                peer.address, e
+            ),
+        }
+    }
+    ranked.sort_by_key(|ranked| (Reverse(ranked.height), ranked.latency));
+    ranked
+}
+
+/// Pick up to `MAX_SYNC_PEERS` of the best ranked peers that are ahead of `local_height`, logging the selection
+pub async fn select_sync_peers(
+    peers: &[PeerHandle],
+    local_height: usize,
+    config: &SyncConfig,
+) -> Vec<PeerHandle> {
+    rank_peers(peers, local_height, config)
+        .await
+        .into_iter()
+        .filter(|ranked| ranked.height > local_height)
+        .take(MAX_SYNC_PEERS)
+        .map(|ranked| {
+            info!(
+                "[SYNC] Selected peer {} at height {} ({} ms)",
+                ranked.peer.address,
+                ranked.height,
+                ranked.latency.as_millis()
+            );
+            ranked.peer
+        })
+        .collect()
+}
+
+/// Ask a peer for the hashes of blocks `start..end`
+async fn fetch_block_hashes(
+    peer: &PeerHandle,
+    start: usize,
+    end: usize,
+    config: &SyncConfig,
+) -> Result<Vec<Hash>, anyhow::Error> {
+    let response = timeout(
+        config.request_timeout,
+        peer.request(Message::new(Command::GetBlockHashes { start, end })),
+    )
+    .await
+    .map_err(|_| anyhow!("Timed out waiting for block hashes"))??;
+
+    match response.command {
+        Command::GetBlockHashesResponse { block_hashes } => Ok(block_hashes),
+        _ => Err(anyhow!(
+            "Could not fetch peer block hashes to sync blockchain"
+        )),
+    }
+}
+
+/// Find the height up to which the local chain and the peer's chain agree.
+/// The local tip is checked first, then the chains are compared in windows of `hash_window` hashes, walking back at most `max_reorg_depth` blocks
+async fn find_fork_point(
+    peer: &PeerHandle,
+    blockchain: &SharedBlockchain,
+    config: &SyncConfig,
+) -> Result<usize, anyhow::Error> {
+    let local_height = blockchain.block_store().get_height();
+    let floor = local_height.saturating_sub(config.max_reorg_depth);
+
+    let mut end = local_height;
+    let mut window = 1;
+    while end > floor {
+        let start = end.saturating_sub(window).max(floor);
+        let peer_hashes = fetch_block_hashes(peer, start, end, config).await?;
+        if peer_hashes.len() != end - start {
+            return Err(anyhow!(
+                "Peer {} returned an incomplete hash window {}..{}",
+                peer.address,
+                start,
+                end
+            ));
+        }
+
+        for (i, peer_hash) in peer_hashes.iter().enumerate().rev() {
+            if blockchain.block_store().get_block_hash_by_height(start + i) == Some(*peer_hash) {
+                return Ok(start + i + 1);
+            }
+        }
+
+        end = start;
+        window = config.hash_window.max(1);
+    }
+
+    if floor == 0 {
+        // Nothing in common, not even the genesis block
+        Ok(0)
+    } else {
+        Err(anyhow!(
+            "No common block with peer {} in the last {} blocks",
+            peer.address,
+            config.max_reorg_depth
+        ))
+    }
+}
+
+/// Fetch the hashes of blocks `start..end` in windows of `hash_window`, sending each window (paired with block heights) down `windows`.
+/// Stops at the first failed or short window, or once the receiver is gone
+async fn fetch_hash_windows(
     peer: PeerHandle,
+    start: usize,
+    end: usize,
+    config: SyncConfig,
+    windows: mpsc::Sender<Result<Vec<(usize, Hash)>, anyhow::Error>>,
+) {
+    let mut height = start;
+    while height < end {
+        let window_end = (height + config.hash_window.max(1)).min(end);
+        let window = match fetch_block_hashes(&peer, height, window_end, &config).await {
+            Ok(hashes) => hashes,
+            Err(e) => {
+                let _ = windows.send(Err(e)).await;
+                return;
+            }
+        };
+
+        let fetched = window.len();
+        let window = window
+            .into_iter()
+            .enumerate()
+            .map(|(i, hash)| (height + i, hash))
+            .collect();
+        if windows.send(Ok(window)).await.is_err() {
+            return;
+        }
+
+        // A short window means the peer has nothing more for us
+        if fetched < window_end - height {
+            return;
+        }
+        height = window_end;
+    }
+}
+
+/// Download a single block by its hash from a peer
+async fn fetch_block(
+    peer: &PeerHandle,
+    hash: Hash,
+    config: &SyncConfig,
+) -> Result<Block, anyhow::Error> {
+    let response = match timeout(
+        config.request_timeout,
+        peer.request(Message::new(Command::GetBlock { block_hash: hash })),
+    )
+    .await
+    {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => {
+            return Err(anyhow!(
+                "Failed to fetch block {} from {}: {:?}",
+                hash.dump_base36(),
+                peer.address,
+                e
+            ));
+        }
+        Err(_) => {
+            return Err(anyhow!(
+                "Timed out fetching block {} from {}",
+                hash.dump_base36(),
+                peer.address
+            ));
+        }
+    };
+
+    match response.command {
+        Command::GetBlockResponse { block: Some(block) } if block.meta.hash == Some(hash) => {
+            Ok(block)
+        }
+        Command::GetBlockResponse { block: Some(_) } => Err(anyhow!(
+            "Peer {} returned a different block than {}",
+            peer.address,
+            hash.dump_base36()
+        )),
+        Command::GetBlockResponse { block: None } => Err(anyhow!(
+            "Peer {} returned empty block {}",
+            peer.address,
+            hash.dump_base36()
+        )),
+        _ => Err(anyhow!(
+            "Unexpected response for block {}",
+            hash.dump_base36()
+        )),
+    }
+}
+
+/// Download a block, retrying with backoff, and moving on to the next holder after `attempts_per_peer` failures.
+/// Every request waits for a free slot in the download window of its peer. The peer of the current attempt is kept in
+/// `serving` under `height`, so a stall is blamed on it rather than on a holder that was already given up on
+async fn fetch_block_with_retry(
+    holders: Vec<PeerHandle>,
+    height: usize,
+    hash: Hash,
+    windows: &HashMap<SocketAddr, DownloadWindow>,
+    serving: &Mutex<HashMap<usize, SocketAddr>>,
+    config: &SyncConfig,
+) -> Result<(PeerHandle, Block), anyhow::Error> {
+    let mut attempt = 0;
+    loop {
+        let peer = &holders[(attempt / config.attempts_per_peer.max(1)) % holders.len()];
+        serving.lock().unwrap().insert(height, peer.address);
+        let slot = windows[&peer.address].acquire().await;
+        let result = fetch_block(peer, hash, config).await;
+        slot.finish(result.is_ok());
+        let error = match result {
+            Ok(block) => return Ok((peer.clone(), block)),
+            Err(e) => e,
+        };
+
+        attempt += 1;
+        if attempt >= config.max_attempts {
+            return Err(error.context(format!("Giving up after {attempt} attempts")));
+        }
+        warn!("[SYNC] {error}, retrying (attempt {attempt})");
+        sleep(config.backoff(attempt)).await;
+    }
+}
+
+/// Sync the local blockchain to the highest of the passed peers.
+/// Block hashes are taken from the highest peer, while the blocks themselves are downloaded in chunks from every peer that has them, and applied in height order.
+/// A failed round is resumed from the current local height, and the sync is only abandoned once `max_stalled_rounds` rounds in a row applied nothing
+pub async fn sync_blockchain(
+    peers: Vec<PeerHandle>,
     blockchain: SharedBlockchain,
+    progress: SharedSyncProgress,
+    config: SyncConfig,
+) -> Result<(), anyhow::Error> {
+    info!("Starting blockchain sync from {} peer(s)", peers.len());
+    progress
+        .write()
+        .await
+        .start(blockchain.block_store().get_height());
+
+    let result = sync_rounds(&peers, &blockchain, &progress, &config).await;
+
+    let mut progress = progress.write().await;
+    if let Err(e) = &result {
+        progress.fail(e.to_string());
+    }
+    progress.finish(blockchain.block_store().get_height());
+    result
+}
+
+/// Run sync rounds until one completes, or too many in a row make no progress
+async fn sync_rounds(
+    peers: &[PeerHandle],
+    blockchain: &SharedBlockchain,
+    progress: &SharedSyncProgress,
+    config: &SyncConfig,
 ) -> Result<(), anyhow::Error> {
-    info!("Starting initial block download");
+    // Windows are kept across rounds, so a resumed round starts from what was learned about each peer
+    let windows = peers
+        .iter()
+        .map(|peer| (peer.address, DownloadWindow::new(config.max_window)))
+        .collect::<HashMap<_, _>>();
+
+    let mut peers = peers.to_vec();
+    let mut round = 0;
+    let mut stalled_rounds = 0;
+    loop {
+        let round_start = blockchain.block_store().get_height();
+        let error = match sync_round(&peers, blockchain, progress, &windows, config, round).await {
+            Ok(()) => {
+                for (address, window) in &windows {
+                    let (min_rtt, rate) = window.stats();
+                    info!(
+                        "[SYNC] Download window of {} settled at {} request(s), min rtt {:?}, {:.1} blocks/s",
+                        address,
+                        window.limit(),
+                        min_rtt.unwrap_or_default(),
+                        rate
+                    );
+                }
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+
+        // Disconnect a peer that holds up the sync, and go on with the others
+        if let Some(stalled) = error.downcast_ref::<Stalled>()
+            && let Some(index) = peers.iter().position(|peer| peer.address == stalled.peer)
+        {
+            let peer = peers.remove(index);
+            warn!("[SYNC] Dropping stalled peer {}", peer.address);
+            let _ = peer.kill(stalled.to_string()).await;
+            if peers.is_empty() {
+                return Err(error.context("No peers left to sync from"));
+            }
+        }
 
+        let height = blockchain.block_store().get_height();
+        if height > round_start {
+            stalled_rounds = 0;
+        } else {
+            stalled_rounds += 1;
+        }
+        if stalled_rounds >= config.max_stalled_rounds {
+            return Err(error);
+        }
+
+        round += 1;
+        warn!("[SYNC] Sync interrupted at height {height}: {error}, resuming");
+        progress.write().await.fail(error.to_string());
+        sleep(config.backoff(stalled_rounds)).await;
+    }
+}
+
+/// A single download pass from the current local height. `round` shifts which peer serves which chunk, so a resumed round does not hit the same peer for the same blocks
+async fn sync_round(
+    peers: &[PeerHandle],
+    blockchain: &SharedBlockchain,
+    progress: &SharedSyncProgress,
+    windows: &HashMap<SocketAddr, DownloadWindow>,
+    config: &SyncConfig,
+    round: usize,
+) -> Result<(), anyhow::Error> {
     let local_height = blockchain.block_store().get_height();
-    let remote_height = match peer
-        .request(Message::new(Command::Ping {
-            height: local_height,
-        }))
-        .await?
-        .command
-    {
-        Command::Pong { height } => height,
-        _ => return Err(anyhow!("Could not fetch peer height to sync blockchain")),
+
+    // Only peers that are ahead of us take part in the download, the best ranked one is trusted for block hashes
+    let sources = rank_peers(peers, local_height, config)
+        .await
+        .into_iter()
+        .filter(|ranked| ranked.height > local_height)
+        .collect::<Vec<_>>();
+
+    let Some(RankedPeer {
+        peer: hash_peer,
+        height: remote_height,
+        ..
+    }) = sources.first()
+    else {
+        info!("[SYNC] No peer is ahead of local height {}", local_height);
+        return Ok(());
     };
+    progress
+        .write()
+        .await
+        .set_target(*remote_height, Some(hash_peer.address));
 
-    let hashes = match peer
-        .request(Message::new(Command::GetBlockHashes {
-            start: local_height,
-            end: remote_height,
-        }))
-        .await?
-        .command
-    {
-        Command::GetBlockHashesResponse { block_hashes } => block_hashes,
-        _ => {
-            return Err(anyhow!(
-                "Could not fetch peer block hashes to sync blockchain"
-            ));
+    // If we are on a stale fork, roll back to the last block we have in common with the hash peer
+    let fork_height = find_fork_point(hash_peer, blockchain, config).await?;
+    if fork_height < local_height {
+        let depth = local_height - fork_height;
+        warn!(
+            "[SYNC] Local chain diverged from {} at height {}, rolling back {} block(s)",
+            hash_peer.address, fork_height, depth
+        );
+        while blockchain.block_store().get_height() > fork_height {
+            blockchain.pop_block()?;
+        }
+        progress
+            .write()
+            .await
+            .reorg(depth, blockchain.block_store().get_height());
+    }
+    let local_height = fork_height;
+
+    info!(
+        "[SYNC] Downloading blocks {}..{} from {} peer(s), hashes from {}",
+        local_height,
+        remote_height,
+        sources.len(),
+        hash_peer.address
+    );
+
+    // Hashes arrive window by window, the next window is fetched while blocks of the current one download
+    let (windows_tx, windows_rx) = mpsc::channel(1);
+    let hash_fetcher = tokio::spawn(fetch_hash_windows(
+        hash_peer.clone(),
+        local_height,
+        *remote_height,
+        config.clone(),
+        windows_tx,
+    ));
+
+    let hashes = stream::unfold(windows_rx, |mut windows_rx| async move {
+        windows_rx.recv().await.map(|window| (window, windows_rx))
+    })
+    .flat_map(|window| {
+        stream::iter(match window {
+            Ok(window) => window.into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        })
+    });
+
+    // Split the hash range into chunks, each chunk goes to the next peer that advertised a height covering it.
+    // The remaining holders follow in order, as fallbacks for retries
+    let holders_of = |height: usize| {
+        let mut holders = sources
+            .iter()
+            .filter(|ranked| ranked.height > height)
+            .map(|ranked| ranked.peer.clone())
+            .collect::<Vec<_>>();
+        if holders.is_empty() {
+            holders.push(hash_peer.clone());
         }
+        let len = holders.len();
+        holders.rotate_left(((height - local_height) / CHUNK_SIZE + round) % len);
+        holders
     };
 
-    info!("[SYNC] Fetched block hashes");
+    // Downloads run concurrently across peers, each peer limited by its own adaptive window. They finish in any order,
+    // so finished blocks wait in the reorder buffer until every block below them is applied
+    let max_in_flight = config.max_window.max(1) * sources.len();
+    let mut hashes = pin!(hashes);
+    let serving = Mutex::new(HashMap::new());
+    let serving = &serving;
+    let mut downloads = FuturesUnordered::new();
+    let mut reorder = ReorderBuffer::new(local_height, config.max_buffered_bytes);
+    let mut more_hashes = true;
+    let mut requested = local_height;
+    let mut last_applied_at = Instant::now();
 
-    // Use FuturesUnordered to buffer block downloads
-    let mut block_futures = FuturesUnordered::new();
-    const BUFFER_SIZE: usize = 10; // max number of blocks to fetch concurrently
+    let result = async {
+        while more_hashes || !downloads.is_empty() {
+            // A full reorder buffer holds back new requests, but never the ones its next block depends on
+            let can_request = more_hashes
+                && downloads.len() < max_in_flight
+                && (downloads.is_empty() || !reorder.is_full());
 
-    for hash in hashes {
-        let peer = peer.clone();
-        // Start the request immediately
-        block_futures.push(async move {
-            let block = match peer
-                .request(Message::new(Command::GetBlock { block_hash: hash }))
-                .await
-            {
-                Ok(resp) => match resp.command {
-                    Command::GetBlockResponse { block } => Ok((hash, block)),
-                    _ => Err(anyhow!(
-                        "Unexpected response for block {}",
-                        hash.dump_base36()
-                    )),
+            tokio::select! {
+                hash = hashes.next(), if can_request => match hash {
+                    Some(hash) => {
+                        let (height, hash) = hash?;
+                        let holders = holders_of(height);
+                        requested = height + 1;
+                        downloads.push(async move {
+                            let result =
+                                fetch_block_with_retry(holders, height, hash, windows, serving, config)
+                                    .await;
+                            (height, result)
+                        });
+                    }
+                    None => more_hashes = false,
                 },
-                Err(e) => Err(anyhow!(
-                    "Failed to fetch block {}: {:?}",
-                    hash.dump_base36(),
-                    e
-                )),
-            };
-            block
-        });
-
-        // Keep the buffer size limited
-        if block_futures.len() >= BUFFER_SIZE {
-            if let Some(result) = block_futures.next().await {
-                match result {
-                    Ok((_hash, Some(block))) => {
-                        blockchain.add_block(block)?
-                    },
-                    Ok((hash, None)) => {
-                        return Err(anyhow!("Peer returned empty block {}", hash.dump_base36()));
+                Some((height, result)) = downloads.next(), if !downloads.is_empty() => {
+                    serving.lock().unwrap().remove(&height);
+                    let (peer, block) = result?;
+                    reorder.insert(height, peer, block);
+
+                    while let Some((peer, block)) = reorder.pop_next() {
+                        let hash = block.meta.hash;
+                        blockchain.add_block(block, true).map_err(|e| {
+                            anyhow!(
+                                "Block {} from {} is invalid: {}",
+                                hash.map(|hash| hash.dump_base36()).unwrap_or_default(),
+                                peer.address,
+                                e
+                            )
+                        })?;
+                        if config.fixed_difficulty {
+                            pin_difficulty(blockchain);
+                        }
+                        progress
+                            .write()
+                            .await
+                            .block_applied(blockchain.block_store().get_height());
+                        last_applied_at = Instant::now();
                     }
-                    Err(e) => return Err(e),
+                    progress
+                        .write()
+                        .await
+                        .set_buffered(reorder.len(), reorder.bytes());
+                }
+                _ = sleep_until((last_applied_at + config.stall_timeout).into()) => {
+                    // Blame the peer currently asked for the block we are waiting for, or the hash peer if it was never requested
+                    let height = reorder.next_height();
+                    let peer = if height < requested {
+                        serving
+                            .lock()
+                            .unwrap()
+                            .get(&height)
+                            .copied()
+                            .unwrap_or(holders_of(height)[0].address)
+                    } else {
+                        hash_peer.address
+                    };
+                    return Err(anyhow::Error::new(Stalled {
+                        peer,
+                        height,
+                        timeout: config.stall_timeout,
+                    }));
                 }
             }
         }
+        Ok::<(), anyhow::Error>(())
     }
+    .await;
 
-    // Finish remaining futures
-    while let Some(result) = block_futures.next().await {
-        match result {
-            Ok((_hash, Some(block))) => blockchain.add_block(block)?,
-            Ok((hash, None)) => {
-                return Err(anyhow!("Peer returned empty block {}", hash.dump_base36()));
-            }
-            Err(e) => return Err(e),
+    hash_fetcher.abort();
+    progress.write().await.set_buffered(0, 0);
+    result
+}
+
+/// Run `sync_blockchain` unless another sync is running, returns `None` then. The mempool is cleared before, as its
+/// transactions may be spent by the synced blocks, and no block is accepted from peers until the sync finishes
+pub async fn run_exclusive_sync(
+    peers: Vec<PeerHandle>,
+    blockchain: &SharedBlockchain,
+    node_state: &SharedNodeState,
+    progress: &SharedSyncProgress,
+    config: &SyncConfig,
+) -> Option<Result<(), anyhow::Error>> {
+    // Check and set under one lock
+    {
+        let mut is_syncing = node_state.is_syncing.write().await;
+        if *is_syncing {
+            return None;
         }
+        *is_syncing = true;
     }
 
-    Ok(())
+    node_state.mempool.clear().await;
+    let res = {
+        let _lock = node_state.processing.lock().await; // Make sure no block gets accepted while we are syncing
+        sync_blockchain(peers, blockchain.clone(), progress.clone(), config.clone()).await
+    };
+    *node_state.is_syncing.write().await = false;
+    Some(res)
+}
+
+/// Start a catch-up daemon, that pings all connected peers every `interval`, and syncs from the highest one once the local chain lags more than `threshold` blocks behind it
+pub fn start_catch_up_sync(
+    interval: Duration,
+    blockchain: SharedBlockchain,
+    node_state: SharedNodeState,
+    sync_progress: SharedSyncProgress,
+    threshold: usize,
+    config: SyncConfig,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            sleep(interval).await;
+
+            if *node_state.is_syncing.read().await {
+                continue;
+            }
+
+            let local_height = blockchain.block_store().get_height();
+            let peers = node_state
+                .connected_peers
+                .read()
+                .await
+                .values()
+                .cloned()
+                .collect::<Vec<_>>();
+            let Some(RankedPeer {
+                peer,
+                height: peer_height,
+                ..
+            }) = rank_peers(&peers, local_height, &config)
+                .await
+                .into_iter()
+                .next()
+            else {
+                continue;
+            };
+
+            if peer_height <= local_height + threshold {
+                continue;
+            }
+
+            info!(
+                "[SYNC] Local height {} is {} blocks behind {}, catching up",
+                local_height,
+                peer_height - local_height,
+                peer.address
+            );
+            // Another sync may have started while we were pinging
+            let Some(res) = run_exclusive_sync(
+                vec![peer],
+                &blockchain,
+                &node_state,
+                &sync_progress,
+                &config,
+            )
+            .await
+            else {
+                continue;
+            };
+
+            info!("Catch-up sync status: {:?}", res);
+        }
+    })
 }
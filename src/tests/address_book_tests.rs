@@ -0,0 +1,82 @@
+use std::{fs, net::SocketAddr};
+
+use crate::{
+    address_book::{
+        ADDRESS_BOOK_FILE, AddressBook, MAX_ENTRIES, PeerEntry, PeerSource, STALE_AFTER,
+    },
+    tests::mock_peer::tmp_path,
+};
+
+const NOW: u64 = 1_700_000_000;
+
+fn addr(addr: &str) -> SocketAddr {
+    addr.parse().unwrap()
+}
+
+#[test]
+fn test_address_book_survives_a_restart() -> Result<(), anyhow::Error> {
+    let node_path = tmp_path("address-book");
+    fs::create_dir_all(&node_path)?;
+
+    let mut book = AddressBook::load(&node_path)?;
+    assert_eq!(book.len(), 0);
+    book.record_success(addr("10.0.0.1:8998"), PeerSource::Seed, NOW);
+    book.record_failure(addr("10.0.0.1:8998"), PeerSource::Discovered, NOW + 10);
+    book.record_seen(addr("10.0.0.2:8998"), PeerSource::Discovered, NOW + 20);
+    book.record_failure(addr("10.0.0.3:8998"), PeerSource::Discovered, NOW + 30);
+    book.save()?;
+
+    let entries: Vec<PeerEntry> =
+        serde_json::from_slice(&fs::read(node_path.join(ADDRESS_BOOK_FILE))?)?;
+    assert_eq!(
+        entries[0],
+        PeerEntry {
+            address: addr("10.0.0.1:8998"),
+            source: PeerSource::Seed,
+            added: NOW,
+            last_seen: Some(NOW),
+            successes: 1,
+            failures: 1,
+        }
+    );
+
+    // The most recently seen first, the ones that mostly failed last
+    let book = AddressBook::load(&node_path)?;
+    assert_eq!(book.len(), 3);
+    assert_eq!(
+        book.candidates(),
+        vec![
+            addr("10.0.0.2:8998"),
+            addr("10.0.0.1:8998"),
+            addr("10.0.0.3:8998")
+        ]
+    );
+
+    fs::write(node_path.join(ADDRESS_BOOK_FILE), "[{")?;
+    let e = AddressBook::load(&node_path).unwrap_err().to_string();
+    assert!(e.contains("Invalid address book"), "{e}");
+
+    fs::remove_dir_all(&node_path)?;
+    Ok(())
+}
+
+#[test]
+fn test_address_book_evicts_stale_peers() {
+    let mut book = AddressBook::empty(&tmp_path("address-book-evict"));
+    let stale = NOW - STALE_AFTER.as_secs() - 1;
+    book.record_success(addr("10.0.0.1:8998"), PeerSource::Seed, stale);
+    book.record_success(addr("10.0.0.1:8998"), PeerSource::Seed, NOW);
+    book.record_success(addr("10.0.0.2:8998"), PeerSource::Discovered, stale);
+    book.record_failure(addr("10.0.0.3:8998"), PeerSource::Discovered, stale);
+    assert_eq!(book.evict_stale(NOW), 2);
+    assert_eq!(book.candidates(), vec![addr("10.0.0.1:8998")]);
+
+    // Beyond the limit, the least recently seen go
+    for i in 0..MAX_ENTRIES as u64 {
+        let address = SocketAddr::from(([10, 1, (i / 256) as u8, (i % 256) as u8], 8998));
+        book.record_seen(address, PeerSource::Discovered, NOW - 1 - i);
+    }
+    assert_eq!(book.evict_stale(NOW), 1);
+    assert_eq!(book.len(), MAX_ENTRIES);
+    assert_eq!(book.candidates()[0], addr("10.0.0.1:8998"));
+}
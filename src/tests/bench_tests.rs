@@ -0,0 +1,100 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    progress::SyncProgress,
+    sync::sync_blockchain,
+    tests::mock_peer::{
+        Faults, MockPeer, TEST_CHAIN_HEIGHT, fast_config, test_chain, tmp_blockchain,
+    },
+};
+
+/// Sync the test chain from `peers` mock peers under `faults`, and report how long it took.
+/// The benchmarks are timing dependent, so they only run with `cargo test bench -- --ignored --nocapture`
+async fn bench_sync(
+    name: &str,
+    peers: usize,
+    faults: Faults,
+) -> Result<(Duration, usize), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain(name);
+    let mut mock_peers = vec![];
+    for seed in 0..peers as u64 {
+        mock_peers.push(
+            MockPeer::start(
+                chain.clone(),
+                Faults {
+                    seed,
+                    ..faults.clone()
+                },
+            )
+            .await,
+        );
+    }
+    let mut handles = vec![];
+    for peer in &mock_peers {
+        handles.push(peer.connect(&blockchain).await);
+    }
+    let progress = SyncProgress::new_shared();
+
+    let started_at = Instant::now();
+    sync_blockchain(handles, blockchain.clone(), progress.clone(), fast_config()).await?;
+    let elapsed = started_at.elapsed();
+
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    assert_eq!(
+        blockchain.block_store().get_last_block_hash(),
+        chain.last().unwrap().meta.hash.unwrap()
+    );
+    let requests = mock_peers
+        .iter()
+        .map(|peer| peer.block_requests())
+        .sum::<usize>();
+    println!(
+        "[bench] {name}: {TEST_CHAIN_HEIGHT} blocks from {peers} peer(s) in {elapsed:?} ({:.1} blocks/s, {requests} block requests)",
+        TEST_CHAIN_HEIGHT as f64 / elapsed.as_secs_f64()
+    );
+    Ok((elapsed, requests))
+}
+
+#[tokio::test]
+#[ignore = "benchmark, run with --ignored"]
+async fn test_bench_sync_local() -> Result<(), anyhow::Error> {
+    bench_sync("bench-local", 1, Faults::default()).await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "benchmark, run with --ignored"]
+async fn test_bench_sync_high_latency() -> Result<(), anyhow::Error> {
+    let faults = Faults {
+        latency: Duration::from_millis(100),
+        jitter: Duration::from_millis(50),
+        ..Faults::default()
+    };
+    let (elapsed, _) = bench_sync("bench-latency", 2, faults).await?;
+
+    // Requests are pipelined, a sync must not cost a full round trip per block
+    assert!(
+        elapsed < Duration::from_millis(150) * TEST_CHAIN_HEIGHT as u32,
+        "Sync took {elapsed:?}, downloads do not seem to overlap"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "benchmark, run with --ignored"]
+async fn test_bench_sync_lossy_reordering_network() -> Result<(), anyhow::Error> {
+    let faults = Faults {
+        latency: Duration::from_millis(20),
+        jitter: Duration::from_millis(80),
+        loss: 0.3,
+        ..Faults::default()
+    };
+    let (_, requests) = bench_sync("bench-lossy", 3, faults).await?;
+
+    assert!(
+        requests > TEST_CHAIN_HEIGHT,
+        "Lost blocks should have been requested again"
+    );
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::fs;
+
+use snap_coin::core::block::Block;
+
+use crate::{
+    archive::ArchiveWriter,
+    bootstrap::bootstrap_from_file,
+    progress::SyncProgress,
+    tests::mock_peer::{TEST_CHAIN_HEIGHT, test_chain, tmp_blockchain, tmp_path},
+};
+
+/// Encode `blocks` as an archive starting at `start_height`
+pub fn archive_bytes(blocks: &[Block], start_height: usize) -> Vec<u8> {
+    let mut archive = ArchiveWriter::new(vec![], start_height, blocks.len()).unwrap();
+    for block in blocks {
+        archive.write_block(block).unwrap();
+    }
+    archive.finish().unwrap()
+}
+
+fn write_archive(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+    let dir = tmp_path(name);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("blocks.snaparc");
+    fs::write(&path, bytes).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn test_bootstrap_from_archive() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("bootstrap");
+    let path = write_archive("bootstrap-file", &archive_bytes(&chain, 0));
+    let progress = SyncProgress::new_shared();
+
+    let applied = bootstrap_from_file(&path, &blockchain, &progress, false).await?;
+
+    assert_eq!(applied, TEST_CHAIN_HEIGHT);
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    assert_eq!(
+        blockchain.block_store().get_last_block_hash(),
+        chain.last().unwrap().meta.hash.unwrap()
+    );
+    let progress = progress.read().await;
+    assert_eq!(progress.applied_height, TEST_CHAIN_HEIGHT);
+    assert_eq!(progress.target_height, TEST_CHAIN_HEIGHT);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bootstrap_skips_known_blocks() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("bootstrap-overlap");
+    for block in &chain[..6] {
+        blockchain.add_block(block.clone(), true)?;
+    }
+    let path = write_archive("bootstrap-overlap-file", &archive_bytes(&chain[4..], 4));
+
+    let applied =
+        bootstrap_from_file(&path, &blockchain, &SyncProgress::new_shared(), false).await?;
+
+    assert_eq!(applied, TEST_CHAIN_HEIGHT - 6);
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bootstrap_rejects_gap() {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("bootstrap-gap");
+    let path = write_archive("bootstrap-gap-file", &archive_bytes(&chain[4..], 4));
+
+    let result = bootstrap_from_file(&path, &blockchain, &SyncProgress::new_shared(), false).await;
+
+    assert!(
+        result.is_err(),
+        "Archive starting above the local chain should fail"
+    );
+    assert_eq!(blockchain.block_store().get_height(), 0);
+}
+
+#[tokio::test]
+async fn test_bootstrap_detects_corruption() {
+    let chain = test_chain().await;
+
+    let mut bytes = archive_bytes(&chain[..3], 0);
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xFF;
+    let blockchain = tmp_blockchain("bootstrap-checksum");
+    let progress = SyncProgress::new_shared();
+    let result = bootstrap_from_file(
+        &write_archive("bootstrap-checksum-file", &bytes),
+        &blockchain,
+        &progress,
+        false,
+    )
+    .await;
+    assert!(result.is_err(), "Checksum mismatch should be reported");
+    assert!(progress.read().await.last_error.is_some());
+
+    let mut bytes = archive_bytes(&chain[..3], 0);
+    bytes[0] = b'X';
+    let result = bootstrap_from_file(
+        &write_archive("bootstrap-magic-file", &bytes),
+        &tmp_blockchain("bootstrap-magic"),
+        &SyncProgress::new_shared(),
+        false,
+    )
+    .await;
+    assert!(
+        result.is_err(),
+        "Files that are not archives should be rejected"
+    );
+
+    let bytes = archive_bytes(&chain[..3], 0);
+    let result = bootstrap_from_file(
+        &write_archive("bootstrap-truncated-file", &bytes[..bytes.len() / 2]),
+        &tmp_blockchain("bootstrap-truncated"),
+        &SyncProgress::new_shared(),
+        false,
+    )
+    .await;
+    assert!(result.is_err(), "Truncated archives should be rejected");
+}
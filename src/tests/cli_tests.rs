@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use crate::{
+    cli::{Command, parse_args},
+    config::NodeConfig,
+};
+
+fn args(args: &str) -> Vec<String> {
+    args.split_whitespace().map(|arg| arg.to_string()).collect()
+}
+
+/// Effective config of `run` with the options in `line`
+fn run_config(line: &str) -> NodeConfig {
+    let Ok(Command::Run(options)) = parse_args(&args(line)) else {
+        panic!("'{line}' should run the node");
+    };
+    options.resolve_with_env(vec![]).unwrap().0
+}
+
+fn parse_error(line: &str) -> String {
+    match parse_args(&args(line)) {
+        Ok(_) => panic!("'{line}' should not parse"),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[test]
+fn test_legacy_flags_run_the_node() {
+    let config = run_config(
+        "--peers a.example:8998,b.example:8998 --node-path ./data --api-port 4000 --headless --no-ibd --sync-stall-timeout 5",
+    );
+    assert_eq!(config.peers, vec!["a.example:8998", "b.example:8998"]);
+    assert_eq!(config.node_path, "./data");
+    assert_eq!(config.api_port, 4000);
+    assert!(config.headless);
+    assert!(config.no_ibd);
+    assert!(!config.no_api);
+    assert_eq!(config.sync_config().stall_timeout, Duration::from_secs(5));
+
+    assert_eq!(run_config("").node_port, 8998);
+}
+
+#[test]
+fn test_inline_values_and_subcommands() {
+    let config = run_config("run --node-port=9000 --reserved-ips=10.0.0.1,::1");
+    assert_eq!(config.node_port, 9000);
+    assert_eq!(config.reserved_ips.len(), 2);
+
+    let Ok(Command::Export(options)) = parse_args(&args("export --from 3 --to 7 --output -"))
+    else {
+        panic!("Expected export");
+    };
+    assert_eq!((options.from, options.to), (3, Some(7)));
+    assert_eq!(options.output.as_deref(), Some("-"));
+
+    let Ok(Command::Verify(options)) = parse_args(&args("verify --reindex")) else {
+        panic!("Expected verify");
+    };
+    assert!(options.reindex);
+
+    let Ok(Command::Status(options)) = parse_args(&args("status --status-port 5000")) else {
+        panic!("Expected status");
+    };
+    assert_eq!((options.api_port(), options.status_port()), (3003, 5000));
+    assert!(matches!(parse_args(&args("peers")), Ok(Command::Peers(_))));
+    let Ok(Command::Reload(options)) = parse_args(&args("reload --network testnet")) else {
+        panic!("Expected reload");
+    };
+    assert_eq!(options.status_port(), 13004);
+}
+
+#[test]
+fn test_invalid_arguments_are_errors() {
+    let error = parse_error("--api-port not-a-port");
+    assert!(error.contains("--api-port"), "{error}");
+    assert!(error.contains("expected a port number"), "{error}");
+
+    let error = parse_error("--node-prot 9000");
+    assert!(error.contains("did you mean '--node-port'?"), "{error}");
+
+    assert!(parse_error("--peers").contains("needs a value"));
+    assert!(parse_error("--headless=yes").contains("expected true or false"));
+    assert!(parse_error("--reserved-ips 10.0.0.300").contains("expected an IP address"));
+    assert!(parse_error("--sync-stall-timeout 0").contains("expected a positive number"));
+    assert!(parse_error("frobnicate").contains("Unknown command"));
+    // Options of other commands are not accepted
+    assert!(parse_error("verify --output x").contains("Unknown option"));
+}
+
+#[test]
+fn test_help_and_version() {
+    let Ok(Command::Help(help)) = parse_args(&args("--help")) else {
+        panic!("Expected help");
+    };
+    assert!(help.contains("Commands:"));
+    assert!(help.contains("export"));
+
+    let Ok(Command::Help(help)) = parse_args(&args("export -h")) else {
+        panic!("Expected help");
+    };
+    assert!(help.contains("--output <file>"));
+    assert!(!help.contains("--peers"));
+
+    assert!(matches!(parse_args(&args("-V")), Ok(Command::Version)));
+    assert!(matches!(
+        parse_args(&args("run --version")),
+        Ok(Command::Version)
+    ));
+}
@@ -0,0 +1,273 @@
+use std::{ffi::OsString, fs};
+
+use crate::{
+    cli::{Command, parse_args},
+    config::{CONFIG_FILE_NAME, NodeArgs, NodeConfig},
+    tests::mock_peer::tmp_path,
+};
+
+fn node_args(line: &str) -> NodeArgs {
+    let args = line
+        .split_whitespace()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<_>>();
+    let Ok(Command::Run(options)) = parse_args(&args) else {
+        panic!("'{line}' should run the node");
+    };
+    options
+}
+
+#[test]
+fn test_toml_settings() {
+    let mut config = NodeConfig::default();
+    config
+        .apply_toml(
+            r#"
+# Seeds
+peers = [
+    "a.example:8998", # first
+    'b.example:8998',
+]
+node-path = "C:\\snap \"node\""
+api_port = 4_000
+headless = true
+max-peers = 8
+seed-ip-preference = "ipv4-only"
+"#,
+        )
+        .unwrap();
+
+    assert_eq!(config.peers, vec!["a.example:8998", "b.example:8998"]);
+    assert_eq!(config.node_path, "C:\\snap \"node\"");
+    assert_eq!(config.api_port, 4000);
+    assert!(config.headless);
+    assert_eq!(config.max_peers, Some(8));
+    assert_eq!(config.seed_ip_preference.to_string(), "ipv4-only");
+    // Unset settings keep their value
+    assert_eq!(config.node_port, NodeConfig::default().node_port);
+}
+
+#[test]
+fn test_toml_errors_name_the_line() {
+    for (text, error) in [
+        ("headless = true\npeers = \"open", "line 2:"),
+        (
+            "headless = true\n[table]",
+            "line 2: 'table': unknown field `table`",
+        ),
+        ("api-port = 1 2", "line 1:"),
+        (
+            "api-port = 1\napi_port = 2",
+            "line 2: 'api-port' is set more than once",
+        ),
+        ("peers = ['a', 'b'", "line 1:"),
+        ("\nheadless = yes", "line 2:"),
+        (
+            "\n\napi-port = 'x'",
+            "line 3: 'api-port': invalid type: string \"x\", expected u16",
+        ),
+    ] {
+        let e = NodeConfig::default()
+            .apply_toml(text)
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains(error), "'{text}' gave '{e}'");
+    }
+}
+
+#[test]
+fn test_config_file_layering() {
+    let node_path = tmp_path("config");
+    fs::create_dir_all(&node_path).unwrap();
+    fs::write(
+        node_path.join(CONFIG_FILE_NAME),
+        "peers = [\"a.example:8998\"]\napi-port = 4000\nnode_port = 9000\nheadless = true\n",
+    )
+    .unwrap();
+
+    // The file in the node path overrides the defaults, the command line overrides the file
+    let (config, sources) = node_args(&format!(
+        "--node-path {} --api-port 5000",
+        node_path.display()
+    ))
+    .resolve_with_env(vec![])
+    .unwrap();
+    assert_eq!(sources.config_file, Some(node_path.join(CONFIG_FILE_NAME)));
+    assert_eq!(config.peers, vec!["a.example:8998"]);
+    assert_eq!(config.api_port, 5000);
+    assert_eq!(config.node_port, 9000);
+    assert!(config.headless);
+    assert_eq!(config.status_port, NodeConfig::default().status_port);
+
+    // Flags set in the file can be turned off again
+    let (config, _) = node_args(&format!(
+        "--node-path {} --headless=false",
+        node_path.display()
+    ))
+    .resolve_with_env(vec![])
+    .unwrap();
+    assert!(!config.headless);
+
+    // --config replaces the file in the node path
+    let other = node_path.join("other.toml");
+    fs::write(&other, "api-port = 6000\n").unwrap();
+    let (config, _) = node_args(&format!(
+        "--node-path {} --config {}",
+        node_path.display(),
+        other.display()
+    ))
+    .resolve_with_env(vec![])
+    .unwrap();
+    assert_eq!(config.api_port, 6000);
+    assert_eq!(config.node_port, NodeConfig::default().node_port);
+
+    fs::remove_dir_all(&node_path).unwrap();
+}
+
+#[test]
+fn test_invalid_config_files() {
+    let dir = tmp_path("config-invalid");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("config.toml");
+
+    for (text, error) in [
+        (
+            "api-port = 70000\n",
+            "line 1: 'api-port': invalid value: integer `70000`, expected u16",
+        ),
+        ("\nsnap-port = 1\n", "line 2: unknown field `snap-port`"),
+        ("config = 'x'\n", "unknown field `config`"),
+        (
+            "reserved-ips = ['10.0.0.1', 'nope']\n",
+            "line 1: 'reserved-ips': invalid IP address syntax",
+        ),
+        ("log-level = 'debug'\n", "expected off, error, warn or info"),
+        (
+            "sync-stall-timeout = 0\n",
+            "line 1: 'sync-stall-timeout': invalid value: integer `0`, expected a nonzero u64",
+        ),
+        (
+            "seed-ip-preference = 'ipv5'\n",
+            "line 1: 'seed-ip-preference': unknown variant `ipv5`, expected one of `any`, `ipv4`, `ipv6`, `ipv4-only`, `ipv6-only`",
+        ),
+    ] {
+        fs::write(&file, text).unwrap();
+        let e = node_args(&format!("--config {}", file.display()))
+            .resolve_with_env(vec![])
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains(error), "'{text}' gave '{e}'");
+    }
+
+    // An explicitly passed file has to exist
+    let e = node_args(&format!("--config {}", dir.join("missing.toml").display()))
+        .resolve_with_env(vec![])
+        .unwrap_err()
+        .to_string();
+    assert!(e.contains("Failed to read config file"), "{e}");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_config_dump_loads_back() {
+    let (config, _) = node_args(
+        "--peers a.example:8998,[::1]:8998 --reserved-ips 10.0.0.1 --node-path ./dump\\\"path --no-ibd --sync-stall-timeout 9 --bootstrap-file chain.snaparc",
+    )
+    .resolve_with_env(vec![])
+    .unwrap();
+
+    let mut loaded = NodeConfig::default();
+    loaded.apply_toml(&config.to_toml()).unwrap();
+    assert_eq!(loaded.to_toml(), config.to_toml());
+    assert_eq!(loaded.node_path, "./dump\\\"path");
+    assert_eq!(loaded.peers, vec!["a.example:8998", "[::1]:8998"]);
+
+    // Unset optional settings are left commented out
+    assert!(
+        NodeConfig::default()
+            .to_toml()
+            .contains("# bootstrap-file =\n")
+    );
+}
+
+fn env(vars: &[(&str, &str)]) -> Vec<(OsString, OsString)> {
+    vars.iter()
+        .map(|(var, value)| (OsString::from(var), OsString::from(value)))
+        .collect()
+}
+
+#[test]
+fn test_env_layering() {
+    let node_path = tmp_path("config-env");
+    fs::create_dir_all(&node_path).unwrap();
+    fs::write(
+        node_path.join(CONFIG_FILE_NAME),
+        "api-port = 4000\nnode-port = 9000\nstatus-port = 9100\n",
+    )
+    .unwrap();
+    let node_path_var = node_path.display().to_string();
+
+    // The environment overrides the file, the command line overrides the environment
+    let (config, sources) = node_args("--node-port 9002")
+        .resolve_with_env(env(&[
+            ("SNAP_NODE_NODE_PATH", &node_path_var),
+            ("SNAP_NODE_API_PORT", "5000"),
+            ("SNAP_NODE_NODE_PORT", "9001"),
+            ("SNAP_NODE_PEERS", "a.example:8998, b.example:8998"),
+            ("SNAP_NODE_RESERVED_IPS", "10.0.0.1,::1"),
+            ("SNAP_NODE_HEADLESS", "true"),
+            ("HOME", "/root"),
+        ]))
+        .unwrap();
+    assert_eq!(sources.config_file, Some(node_path.join(CONFIG_FILE_NAME)));
+    assert_eq!(config.status_port, 9100);
+    assert_eq!(config.api_port, 5000);
+    assert_eq!(config.node_port, 9002);
+    assert_eq!(config.peers, vec!["a.example:8998", "b.example:8998"]);
+    assert_eq!(config.reserved_ips.len(), 2);
+    assert!(config.headless);
+    assert_eq!(sources.env_vars.len(), 6);
+
+    // SNAP_NODE_CONFIG picks the file, and an empty list clears the peers of the file
+    let other = node_path.join("other.toml");
+    fs::write(&other, "api-port = 6000\npeers = ['a.example:8998']\n").unwrap();
+    let (config, _) = node_args("")
+        .resolve_with_env(env(&[
+            ("SNAP_NODE_CONFIG", &other.display().to_string()),
+            ("SNAP_NODE_PEERS", ""),
+        ]))
+        .unwrap();
+    assert_eq!(config.api_port, 6000);
+    assert!(config.peers.is_empty());
+
+    fs::remove_dir_all(&node_path).unwrap();
+}
+
+#[test]
+fn test_invalid_env_vars() {
+    for (var, value, error) in [
+        (
+            "SNAP_NODE_API_PORT",
+            "http",
+            "Invalid environment variable SNAP_NODE_API_PORT: Invalid value 'http' for '--api-port'",
+        ),
+        (
+            "SNAP_NODE_API_PROT",
+            "3003",
+            "Unknown environment variable SNAP_NODE_API_PROT, did you mean SNAP_NODE_API_PORT?",
+        ),
+        ("SNAP_NODE_HEADLESS", "yes", "expected true or false"),
+        (
+            "SNAP_NODE_RESERVED_IPS",
+            "10.0.0.1,localhost",
+            "expected an IP address",
+        ),
+    ] {
+        let e = node_args("")
+            .resolve_with_env(env(&[(var, value)]))
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains(error), "{var}={value} gave '{e}'");
+    }
+}
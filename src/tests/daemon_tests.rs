@@ -0,0 +1,103 @@
+use std::{fs, path::Path, process, time::Duration};
+
+use snap_coin::full_node::node_state::NodeState;
+use tokio::{net::UnixDatagram, time::timeout};
+
+use crate::{
+    cli::{Command, parse_args},
+    daemon::{Notifier, PidFile, start_readiness_notifier},
+    progress::SyncProgress,
+    tests::mock_peer::{tmp_blockchain, tmp_path},
+};
+
+/// Next message sent to `socket`
+async fn receive(socket: &UnixDatagram) -> String {
+    let mut buffer = [0; 256];
+    let len = timeout(Duration::from_secs(10), socket.recv(&mut buffer))
+        .await
+        .expect("No notification within 10s")
+        .unwrap();
+    String::from_utf8_lossy(&buffer[..len]).to_string()
+}
+
+#[test]
+fn test_pid_file() -> Result<(), anyhow::Error> {
+    let pid_file_of = |line: &str| {
+        let args = line
+            .split_whitespace()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>();
+        let Ok(Command::Run(args)) = parse_args(&args) else {
+            panic!("'{line}' should run the node");
+        };
+        args.resolve_with_env(vec![]).unwrap().0.pid_file()
+    };
+    assert_eq!(pid_file_of("--node-path /tmp/node"), None);
+    assert_eq!(
+        pid_file_of("--node-path /tmp/node --daemon"),
+        Some(Path::new("/tmp/node/node.pid").to_path_buf())
+    );
+    assert_eq!(
+        pid_file_of("--daemon --pid-file /run/node.pid"),
+        Some(Path::new("/run/node.pid").to_path_buf())
+    );
+
+    let dir = tmp_path("pid-file");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join("node.pid");
+    let pid_file = PidFile::create(&path)?;
+    assert_eq!(fs::read_to_string(&path)?, format!("{}\n", process::id()));
+    drop(pid_file);
+    assert!(!path.exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_readiness_notifications() -> Result<(), anyhow::Error> {
+    let dir = tmp_path("notify");
+    fs::create_dir_all(&dir)?;
+    let socket_path = dir.join("notify.sock");
+    let socket = UnixDatagram::bind(&socket_path)?;
+
+    let node_state = NodeState::new_empty();
+    *node_state.is_syncing.write().await = true;
+    let progress = SyncProgress::new_shared();
+    {
+        let mut progress = progress.write().await;
+        progress.start(3);
+        progress.set_target(10, None);
+    }
+    let notifier = start_readiness_notifier(
+        Notifier::new(Some(socket_path.into_os_string())),
+        tmp_blockchain("notify"),
+        node_state.clone(),
+        progress,
+    );
+
+    // Not ready while the initial sync runs
+    assert_eq!(receive(&socket).await, "STATUS=Syncing 3/10");
+    *node_state.is_syncing.write().await = false;
+    assert_eq!(
+        receive(&socket).await,
+        "READY=1\nSTATUS=Height 0, 0 peer(s)"
+    );
+    notifier.abort();
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_abstract_notify_socket() -> Result<(), anyhow::Error> {
+    use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+    let name = format!("snap-coin-node-notify-{}", process::id());
+    let socket = std::os::unix::net::UnixDatagram::bind_addr(&SocketAddr::from_abstract_name(
+        name.as_bytes(),
+    )?)?;
+    socket.set_nonblocking(true)?;
+    let socket = UnixDatagram::from_std(socket)?;
+
+    Notifier::new(Some(format!("@{name}").into())).notify("STOPPING=1");
+    assert_eq!(receive(&socket).await, "STOPPING=1");
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use crate::{
+    archive::{ArchiveReader, ArchiveWriter},
+    bootstrap::bootstrap_from_file,
+    export::export_blocks,
+    progress::SyncProgress,
+    tests::mock_peer::{TEST_CHAIN_HEIGHT, test_chain, tmp_blockchain, tmp_path},
+};
+
+#[tokio::test]
+async fn test_export_round_trip() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let source = tmp_blockchain("export-source");
+    for block in &chain {
+        source.add_block(block.clone(), true)?;
+    }
+
+    let mut bytes = vec![];
+    assert_eq!(export_blocks(&source, 3, 9, &mut bytes)?, 6);
+
+    let mut archive = ArchiveReader::open(&bytes[..])?;
+    assert_eq!(archive.header().start_height, 3);
+    assert_eq!(archive.header().block_count, 6);
+    let mut hashes = vec![];
+    while let Some(block) = archive.next_block()? {
+        hashes.push(block.meta.hash);
+    }
+    assert_eq!(
+        hashes,
+        chain[3..9]
+            .iter()
+            .map(|block| block.meta.hash)
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exported_archive_bootstraps_new_node() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let source = tmp_blockchain("export-seed");
+    for block in &chain {
+        source.add_block(block.clone(), true)?;
+    }
+    let dir = tmp_path("export-file");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("chain.snaparc");
+    export_blocks(&source, 0, TEST_CHAIN_HEIGHT, std::fs::File::create(&path)?)?;
+
+    let target = tmp_blockchain("export-target");
+    bootstrap_from_file(&path, &target, &SyncProgress::new_shared(), false).await?;
+    assert_eq!(
+        target.block_store().get_last_block_hash(),
+        source.block_store().get_last_block_hash()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_rejects_invalid_range() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let source = tmp_blockchain("export-range");
+    for block in &chain[..4] {
+        source.add_block(block.clone(), true)?;
+    }
+
+    assert!(export_blocks(&source, 0, 5, vec![]).is_err());
+    assert!(export_blocks(&source, 3, 2, vec![]).is_err());
+    assert_eq!(export_blocks(&source, 4, 4, vec![])?, 0);
+    Ok(())
+}
+
+#[test]
+fn test_archive_writer_checks_block_count() {
+    let archive = ArchiveWriter::new(vec![], 0, 1).unwrap();
+    assert!(
+        archive.finish().is_err(),
+        "Announced blocks were never written"
+    );
+}
@@ -0,0 +1,45 @@
+use std::{fs, process};
+
+use crate::{
+    lock::{LOCK_FILE, NodePathLock},
+    tests::mock_peer::tmp_path,
+    verify::{VerifyOptions, run_verify},
+};
+
+#[test]
+fn test_node_path_lock() -> Result<(), anyhow::Error> {
+    let node_path = tmp_path("lock");
+    fs::create_dir_all(node_path.join("blockchain"))?;
+
+    let lock = NodePathLock::acquire(&node_path)?;
+    let contents = fs::read_to_string(node_path.join(LOCK_FILE))?;
+    assert!(contents.contains(&format!("pid={}", process::id())));
+
+    // A second node, or an offline command, is turned away while the lock is held
+    let error = NodePathLock::acquire(&node_path).unwrap_err().to_string();
+    assert!(error.contains("is in use by another node"), "{error}");
+    assert!(
+        error.contains(&format!("process {}", process::id())),
+        "{error}"
+    );
+    let error = run_verify(VerifyOptions {
+        node_path: Some(node_path.to_str().unwrap().to_string()),
+        ..VerifyOptions::default()
+    })
+    .unwrap_err()
+    .to_string();
+    assert!(error.contains("is in use by another node"), "{error}");
+
+    // Released on drop
+    drop(lock);
+    assert_eq!(fs::read_to_string(node_path.join(LOCK_FILE))?, "");
+    assert!(NodePathLock::acquire(&node_path)?.stale.is_none());
+
+    // An owner left behind by a crash does not hold the node path
+    fs::write(node_path.join(LOCK_FILE), "pid=4194304\nstarted=1\n")?;
+    let lock = NodePathLock::acquire(&node_path)?;
+    assert_eq!(lock.stale.as_ref().map(|owner| owner.pid), Some(4194304));
+    let contents = fs::read_to_string(node_path.join(LOCK_FILE))?;
+    assert!(contents.contains(&format!("pid={}", process::id())));
+    Ok(())
+}
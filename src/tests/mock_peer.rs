@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use snap_coin::{
+    build_block,
+    core::{block::Block, blockchain::Blockchain},
+    crypto::Hash,
+    economics::DEV_WALLET,
+    full_node::{SharedBlockchain, connect_peer, node_state::NodeState},
+    node::{
+        message::{Command, Message},
+        peer::PeerHandle,
+    },
+};
+use tokio::{
+    net::TcpListener,
+    sync::{Mutex, OnceCell},
+    time::sleep,
+};
+
+use crate::sync::SyncConfig;
+
+/// Height of the shared test chain
+pub const TEST_CHAIN_HEIGHT: usize = 12;
+
+static TEST_CHAIN: OnceCell<Vec<Block>> = OnceCell::const_new();
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A fresh, unique directory under the system temp dir
+pub fn tmp_path(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!(
+        "snap-coin-node-{}-{}-{}",
+        name,
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    let _ = fs::remove_dir_all(&path);
+    path
+}
+
+/// An empty blockchain in a fresh temp dir
+pub fn tmp_blockchain(name: &str) -> SharedBlockchain {
+    Arc::new(Blockchain::new(tmp_path(name).to_str().unwrap()))
+}
+
+/// A valid chain of `TEST_CHAIN_HEIGHT` blocks, mined once and shared by all tests
+pub async fn test_chain() -> Vec<Block> {
+    TEST_CHAIN
+        .get_or_init(|| async {
+            let blockchain = tmp_blockchain("source");
+            let mut blocks = vec![];
+            for _ in 0..TEST_CHAIN_HEIGHT {
+                let mut block = build_block(&*blockchain, &vec![], DEV_WALLET)
+                    .await
+                    .unwrap();
+                #[allow(deprecated)]
+                block.compute_pow().unwrap();
+                blockchain.add_block(block.clone(), false).unwrap();
+                blocks.push(block);
+            }
+            blocks
+        })
+        .await
+        .clone()
+}
+
+/// Short timings, so failures are retried quickly. The timeout leaves room for block validation hogging the test runtime
+pub fn fast_config() -> SyncConfig {
+    SyncConfig {
+        retry_backoff: Duration::from_millis(10),
+        max_retry_backoff: Duration::from_millis(50),
+        request_timeout: Duration::from_secs(1),
+        ..SyncConfig::default()
+    }
+}
+
+/// Network conditions and faults of a mock peer. The `*_every` faults hit every `n`th `GetBlock` request (counted from 1)
+#[derive(Clone, Default)]
+pub struct Faults {
+    /// Added to every response
+    pub latency: Duration,
+    /// Random extra delay of up to this much on every response, reorders responses
+    pub jitter: Duration,
+    /// Chance (`0.0..=1.0`) that a `GetBlock` response is lost
+    pub loss: f64,
+    /// Seed of the random jitter and loss, so runs are reproducible
+    pub seed: u64,
+    /// Never answer every `n`th request
+    pub drop_every: Option<usize>,
+    /// Answer every `n`th request with a tampered block (hash kept, contents changed)
+    pub corrupt_every: Option<usize>,
+    /// Answer every `n`th request with no block
+    pub empty_every: Option<usize>,
+    /// Answer every `n`th request only after `delay`, so later requests overtake it
+    pub delay_every: Option<usize>,
+    pub delay: Duration,
+    /// Stop answering `GetBlock` after this many requests, while still answering everything else
+    pub stall_after: Option<usize>,
+    /// Report this height to pings, instead of the real one
+    pub advertised_height: Option<usize>,
+}
+
+impl Faults {
+    fn hits(every: Option<usize>, n: usize) -> bool {
+        every.is_some_and(|every| n.is_multiple_of(every))
+    }
+}
+
+/// Tiny xorshift generator, plenty for jitter and loss
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    /// Uniform in `0.0..1.0`
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Chain served by a mock peer
+#[derive(Default)]
+struct MockChain {
+    by_hash: HashMap<Hash, Block>,
+    hashes: Vec<Hash>,
+}
+
+impl MockChain {
+    fn extend(&mut self, blocks: Vec<Block>) {
+        for block in blocks {
+            let hash = block.meta.hash.unwrap();
+            self.hashes.push(hash);
+            self.by_hash.insert(hash, block);
+        }
+    }
+}
+
+/// A peer serving `Ping`, `GetBlockHashes` and `GetBlock` from a fixed chain, straight over the P2P protocol
+pub struct MockPeer {
+    pub address: SocketAddr,
+    chain: Arc<RwLock<MockChain>>,
+    block_requests: Arc<AtomicUsize>,
+    hash_requests: Arc<AtomicUsize>,
+}
+
+impl MockPeer {
+    /// Start listening on a random local port
+    pub async fn start(blocks: Vec<Block>, faults: Faults) -> MockPeer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let block_requests = Arc::new(AtomicUsize::new(0));
+        let hash_requests = Arc::new(AtomicUsize::new(0));
+
+        let chain = Arc::new(RwLock::new(MockChain::default()));
+        chain.write().unwrap().extend(blocks);
+
+        let (served, counter, hash_counter) =
+            (chain.clone(), block_requests.clone(), hash_requests.clone());
+        tokio::spawn(async move {
+            let mut connections = 0u64;
+            while let Ok((stream, _)) = listener.accept().await {
+                connections += 1;
+                let mut rng = Rng::new(faults.seed ^ connections);
+                let (chain, faults, counter, hash_counter) = (
+                    served.clone(),
+                    faults.clone(),
+                    counter.clone(),
+                    hash_counter.clone(),
+                );
+                tokio::spawn(async move {
+                    let (mut reader, writer) = stream.into_split();
+                    let writer = Arc::new(Mutex::new(writer));
+                    while let Ok(message) = Message::from_stream(&mut reader).await {
+                        let mut delay = faults.latency + faults.jitter.mul_f64(rng.next_f64());
+                        let command = match message.command {
+                            Command::Ping { .. } => Command::Pong {
+                                height: faults
+                                    .advertised_height
+                                    .unwrap_or(chain.read().unwrap().hashes.len()),
+                            },
+                            Command::GetBlockHashes { start, end } => {
+                                hash_counter.fetch_add(1, Ordering::SeqCst);
+                                Command::GetBlockHashesResponse {
+                                    block_hashes: {
+                                        let hashes = &chain.read().unwrap().hashes;
+                                        hashes
+                                            .get(start..end.min(hashes.len()))
+                                            .unwrap_or_default()
+                                            .to_vec()
+                                    },
+                                }
+                            }
+                            Command::GetBlock { block_hash } => {
+                                let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                                if Faults::hits(faults.drop_every, n)
+                                    || faults.stall_after.is_some_and(|after| n > after)
+                                    || rng.next_f64() < faults.loss
+                                {
+                                    continue;
+                                }
+                                if Faults::hits(faults.delay_every, n) {
+                                    delay += faults.delay;
+                                }
+                                let mut block =
+                                    chain.read().unwrap().by_hash.get(&block_hash).cloned();
+                                if Faults::hits(faults.empty_every, n) {
+                                    block = None;
+                                }
+                                if Faults::hits(faults.corrupt_every, n)
+                                    && let Some(block) = &mut block
+                                {
+                                    block.nonce = block.nonce.wrapping_add(1);
+                                }
+                                Command::GetBlockResponse { block }
+                            }
+                            _ => continue,
+                        };
+                        // Answered from a separate task, so a delayed response does not hold back the ones after it
+                        let response = message.make_response(command);
+                        let writer = writer.clone();
+                        tokio::spawn(async move {
+                            sleep(delay).await;
+                            let _ = response.send(&mut *writer.lock().await).await;
+                        });
+                    }
+                });
+            }
+        });
+
+        MockPeer {
+            address,
+            chain,
+            block_requests,
+            hash_requests,
+        }
+    }
+
+    /// Append `blocks` to the served chain, as if the peer received them
+    pub fn extend(&self, blocks: Vec<Block>) {
+        self.chain.write().unwrap().extend(blocks);
+    }
+
+    /// Amount of `GetBlock` requests received so far
+    pub fn block_requests(&self) -> usize {
+        self.block_requests.load(Ordering::SeqCst)
+    }
+
+    /// Amount of `GetBlockHashes` requests received so far
+    pub fn hash_requests(&self) -> usize {
+        self.hash_requests.load(Ordering::SeqCst)
+    }
+
+    /// Connect the syncing node to this mock peer
+    pub async fn connect(&self, blockchain: &SharedBlockchain) -> PeerHandle {
+        connect_peer(self.address, blockchain, &NodeState::new_empty())
+            .await
+            .unwrap()
+    }
+}
@@ -0,0 +1,37 @@
+mod address_book_tests;
+
+mod bench_tests;
+
+mod bootstrap_tests;
+
+mod cli_tests;
+
+mod config_tests;
+
+mod daemon_tests;
+
+mod export_tests;
+
+mod lock_tests;
+
+mod mock_peer;
+
+mod network_tests;
+
+mod reload_tests;
+
+mod reorder_tests;
+
+mod seeds_tests;
+
+mod shutdown_tests;
+
+mod status_tests;
+
+mod sync_tests;
+
+mod verify_tests;
+
+mod watchdog_tests;
+
+mod window_tests;
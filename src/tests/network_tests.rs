@@ -0,0 +1,166 @@
+use std::fs;
+
+use snap_coin::{
+    build_block,
+    core::{block::Block, blockchain::Blockchain, difficulty::STARTING_BLOCK_DIFFICULTY},
+    economics::DEV_WALLET,
+    full_node::{accept_block, node_state::NodeState},
+};
+use tokio::task::yield_now;
+
+use crate::{
+    cli::{Command, parse_args},
+    config::NodeArgs,
+    network::{Network, check_node_path, pin_difficulty, start_difficulty_pinning},
+    tests::mock_peer::{tmp_blockchain, tmp_path},
+    verify::verify_chain,
+};
+
+/// Blocks past the point where an unpinned difficulty would make mining slow
+const REGTEST_CHAIN_HEIGHT: usize = 40;
+
+fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(|arg| arg.to_string()).collect()
+}
+
+fn node_args(line: &str) -> NodeArgs {
+    let Ok(Command::Run(options)) = parse_args(&args(line)) else {
+        panic!("'{line}' should run the node");
+    };
+    options
+}
+
+/// Mine a regtest chain into `blockchain`, pinning the difficulty after every block like a regtest node
+async fn mine_regtest_chain(blockchain: &Blockchain, height: usize) -> Vec<Block> {
+    let mut blocks = vec![];
+    for _ in 0..height {
+        let mut block = build_block(blockchain, &vec![], DEV_WALLET).await.unwrap();
+        #[allow(deprecated)]
+        block.compute_pow().unwrap();
+        blockchain.add_block(block.clone(), false).unwrap();
+        pin_difficulty(blockchain);
+        blocks.push(block);
+    }
+    blocks
+}
+
+#[test]
+fn test_network_defaults() {
+    let (config, _) = node_args("--network regtest")
+        .resolve_with_env(vec![])
+        .unwrap();
+    assert_eq!(config.network, Network::Regtest);
+    assert_eq!(config.node_path, "./node-regtest");
+    assert_eq!(
+        (config.node_port, config.api_port, config.status_port),
+        (28998, 23003, 23004)
+    );
+    assert!(config.sync_config().fixed_difficulty);
+
+    // Explicit settings win over the network defaults, wherever the network is picked
+    let (config, _) = node_args("--node-port 9000")
+        .resolve_with_env(vec![("SNAP_NODE_NETWORK".into(), "testnet".into())])
+        .unwrap();
+    assert_eq!(config.network, Network::Testnet);
+    assert_eq!((config.node_port, config.api_port), (9000, 13003));
+    assert!(!config.sync_config().fixed_difficulty);
+
+    let dir = tmp_path("network-file");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("regtest.toml");
+    fs::write(&file, "api-port = 4000\nnetwork = \"regtest\"\n").unwrap();
+    let (config, _) = node_args(&format!("--config {}", file.display()))
+        .resolve_with_env(vec![])
+        .unwrap();
+    assert_eq!(config.network, Network::Regtest);
+    assert_eq!((config.api_port, config.node_port), (4000, 28998));
+    fs::remove_dir_all(&dir).unwrap();
+
+    let Ok(Command::Status(options)) = parse_args(&args("status --network regtest")) else {
+        panic!("Expected status");
+    };
+    assert_eq!((options.api_port(), options.status_port()), (23003, 23004));
+    let Ok(Command::Export(options)) = parse_args(&args("export --network testnet")) else {
+        panic!("Expected export");
+    };
+    assert_eq!(options.node_path(), "./node-testnet");
+
+    assert!(
+        parse_args(&args("--network devnet"))
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("expected mainnet, testnet or regtest")
+    );
+}
+
+#[test]
+fn test_node_paths_are_tied_to_a_network() {
+    let node_path = tmp_path("network-marker");
+    check_node_path(&node_path, Network::Regtest).unwrap();
+    check_node_path(&node_path, Network::Regtest).unwrap();
+    let e = check_node_path(&node_path, Network::Mainnet)
+        .unwrap_err()
+        .to_string();
+    assert!(e.contains("holds regtest data"), "{e}");
+    fs::remove_dir_all(&node_path).unwrap();
+
+    // Data from before network markers is mainnet data, regtest may never touch it
+    let legacy = tmp_path("network-legacy");
+    fs::create_dir_all(legacy.join("blockchain")).unwrap();
+    let e = check_node_path(&legacy, Network::Regtest)
+        .unwrap_err()
+        .to_string();
+    assert!(e.contains("holds mainnet data"), "{e}");
+    check_node_path(&legacy, Network::Mainnet).unwrap();
+    assert_eq!(
+        fs::read_to_string(legacy.join("network")).unwrap().trim(),
+        "mainnet"
+    );
+    fs::remove_dir_all(&legacy).unwrap();
+}
+
+#[tokio::test]
+async fn test_regtest_difficulty_stays_trivial() {
+    let node_path = tmp_path("regtest-chain");
+    let blockchain = Blockchain::new(node_path.join("blockchain").to_str().unwrap());
+    mine_regtest_chain(&blockchain, REGTEST_CHAIN_HEIGHT).await;
+    assert_eq!(blockchain.block_store().get_height(), REGTEST_CHAIN_HEIGHT);
+    assert_eq!(blockchain.get_block_difficulty(), STARTING_BLOCK_DIFFICULTY);
+
+    // Only a node that pins the difficulty too accepts the chain
+    let report = verify_chain(&blockchain, &node_path.join("scratch"), true).unwrap();
+    assert!(report.first_invalid.is_none());
+    let report = verify_chain(&blockchain, &node_path.join("scratch"), false).unwrap();
+    assert!(report.first_invalid.is_some());
+
+    drop(blockchain);
+    fs::remove_dir_all(&node_path).unwrap();
+}
+
+#[tokio::test]
+async fn test_back_to_back_regtest_blocks() {
+    let blockchain = tmp_blockchain("regtest-back-to-back");
+    let node_state = NodeState::new_empty();
+    let pinning = start_difficulty_pinning(blockchain.clone(), node_state.clone());
+
+    // Submitted like a miner would through the API, every block built right after the last one was accepted
+    for _ in 0..REGTEST_CHAIN_HEIGHT {
+        let mut block = build_block(&*blockchain, &vec![], DEV_WALLET)
+            .await
+            .unwrap();
+        assert_eq!(block.meta.block_pow_difficulty, STARTING_BLOCK_DIFFICULTY);
+        #[allow(deprecated)]
+        block.compute_pow().unwrap();
+        accept_block(&blockchain, &node_state, block).await.unwrap();
+        // The round trip to the miner, which is all the pinning task needs to catch up
+        yield_now().await;
+    }
+    assert_eq!(blockchain.block_store().get_height(), REGTEST_CHAIN_HEIGHT);
+
+    // Every block was built against the pinned difficulty, so nodes that pin too accept the chain
+    let scratch = tmp_path("regtest-back-to-back-scratch");
+    let report = verify_chain(&blockchain, &scratch, true).unwrap();
+    assert!(report.first_invalid.is_none());
+    pinning.abort();
+}
@@ -0,0 +1,136 @@
+use std::{fs, time::Duration};
+
+use log::LevelFilter;
+use snap_coin::full_node::{connect_peer, node_state::NodeState};
+use tokio::{sync::watch, time::sleep};
+
+use crate::{
+    address_book::AddressBook,
+    cli::{Command, parse_args},
+    config::{NodeArgs, NodeConfig},
+    peers::start_peer_limit,
+    reload::{Reloader, RuntimeSettings, restart_required},
+    seeds::IpPreference,
+    tests::mock_peer::{Faults, MockPeer, test_chain, tmp_blockchain, tmp_path},
+};
+
+fn node_args(line: &str) -> NodeArgs {
+    let args = line
+        .split_whitespace()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<_>>();
+    let Ok(Command::Run(options)) = parse_args(&args) else {
+        panic!("'{line}' should run the node");
+    };
+    options
+}
+
+#[test]
+fn test_restart_required() {
+    let running = NodeConfig::default();
+    let mut new = running.clone();
+    new.peers = vec!["127.0.0.1:1".to_string()];
+    new.seed_ip_preference = IpPreference::Ipv6Only;
+    new.max_peers = Some(8);
+    new.log_level = LevelFilter::Warn;
+    assert!(restart_required(&running, &new).is_empty());
+
+    new.api_port = 4000;
+    new.no_catch_up = true;
+    assert_eq!(
+        restart_required(&running, &new),
+        vec!["api-port", "no-catch-up"]
+    );
+}
+
+#[tokio::test]
+async fn test_reload_applies_runtime_settings() -> Result<(), anyhow::Error> {
+    let dir = tmp_path("reload");
+    fs::create_dir_all(&dir)?;
+    let file = dir.join("config.toml");
+    fs::write(&file, "max-peers = 8\n")?;
+
+    let args = node_args(&format!("--config {}", file.display()));
+    let (running, _) = args.resolve()?;
+    let (sender, settings) = watch::channel(RuntimeSettings::from_config(&running).await?);
+    let blockchain = tmp_blockchain("reload");
+    let node_state = NodeState::new_empty();
+    let reloader = Reloader::new(
+        args,
+        running,
+        sender,
+        blockchain.clone(),
+        node_state.clone(),
+        AddressBook::empty(&dir).into_shared(),
+    );
+
+    assert_eq!(reloader.reload().await?, "No runtime settings changed");
+
+    let peer = MockPeer::start(test_chain().await, Faults::default()).await;
+    fs::write(
+        &file,
+        format!(
+            "peers = ['{}']\nreserved-ips = ['10.0.0.1']\nlog-level = 'info'\napi-port = 4000\n",
+            peer.address
+        ),
+    )?;
+    let summary = reloader.reload().await?;
+    assert_eq!(
+        summary,
+        "Applied reserved IPs, no peer limit, seed peers (1 newly connected). Needs a restart: api-port"
+    );
+    assert!(
+        node_state
+            .connected_peers
+            .read()
+            .await
+            .contains_key(&peer.address)
+    );
+    let current = settings.borrow().clone();
+    assert_eq!(current.seeds, vec![peer.address]);
+    assert_eq!(
+        current.reserved_ips,
+        vec!["10.0.0.1".parse::<std::net::IpAddr>()?]
+    );
+    assert_eq!(current.max_peers, None);
+
+    // An invalid config changes nothing
+    fs::write(&file, "max-peers = 'many'\n")?;
+    let e = reloader.reload().await.unwrap_err().to_string();
+    assert!(e.contains("'max-peers'"), "{e}");
+    assert_eq!(*settings.borrow(), current);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_peer_limit() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("peer-limit");
+    let node_state = NodeState::new_empty();
+    for _ in 0..3 {
+        let peer = MockPeer::start(chain.clone(), Faults::default()).await;
+        connect_peer(peer.address, &blockchain, &node_state).await?;
+    }
+
+    let (sender, settings) = watch::channel(RuntimeSettings {
+        seed_hosts: vec![],
+        ip_preference: IpPreference::Any,
+        seeds: vec![],
+        reserved_ips: vec![],
+        max_peers: None,
+        min_peers: 0,
+        log_level: LevelFilter::Info,
+    });
+    let limit = start_peer_limit(node_state.clone(), settings);
+    sleep(Duration::from_secs(2)).await;
+    assert_eq!(node_state.connected_peers.read().await.len(), 3);
+
+    // Lowered by a reload
+    sender.send_modify(|settings| settings.max_peers = Some(1));
+    sleep(Duration::from_secs(3)).await;
+    assert_eq!(node_state.connected_peers.read().await.len(), 1);
+    limit.abort();
+    Ok(())
+}
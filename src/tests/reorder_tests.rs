@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use crate::{
+    progress::SyncProgress,
+    reorder::{ReorderBuffer, block_size},
+    sync::{SyncConfig, sync_blockchain},
+    tests::mock_peer::{Faults, MockPeer, TEST_CHAIN_HEIGHT, test_chain, tmp_blockchain},
+};
+
+#[tokio::test]
+async fn test_reorder_buffer_releases_in_sequence() {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("reorder-buffer");
+    let peer = MockPeer::start(vec![], Faults::default())
+        .await
+        .connect(&blockchain)
+        .await;
+    let mut reorder = ReorderBuffer::new(3, usize::MAX);
+
+    reorder.insert(5, peer.clone(), chain[5].clone());
+    reorder.insert(4, peer.clone(), chain[4].clone());
+    assert!(reorder.pop_next().is_none(), "Block 3 is still missing");
+    assert_eq!(reorder.len(), 2);
+
+    reorder.insert(3, peer.clone(), chain[3].clone());
+    let released = std::iter::from_fn(|| reorder.pop_next())
+        .map(|(_, block)| block.meta.hash)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        released,
+        chain[3..6]
+            .iter()
+            .map(|block| block.meta.hash)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(reorder.bytes(), 0);
+
+    // Blocks that were already released are ignored
+    reorder.insert(4, peer, chain[4].clone());
+    assert_eq!(reorder.len(), 0);
+}
+
+#[tokio::test]
+async fn test_reorder_buffer_reports_full() {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("reorder-full");
+    let peer = MockPeer::start(vec![], Faults::default())
+        .await
+        .connect(&blockchain)
+        .await;
+    let mut reorder = ReorderBuffer::new(0, block_size(&chain[1]) + 1);
+
+    reorder.insert(1, peer.clone(), chain[1].clone());
+    assert!(!reorder.is_full());
+    reorder.insert(2, peer.clone(), chain[2].clone());
+    assert!(reorder.is_full());
+
+    reorder.insert(0, peer, chain[0].clone());
+    reorder.pop_next();
+    reorder.pop_next();
+    assert!(!reorder.is_full());
+}
+
+#[tokio::test]
+async fn test_sync_applies_overtaken_blocks_in_order() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("overtaken");
+    // Every other block arrives late, after the one above it
+    let peer = MockPeer::start(
+        chain.clone(),
+        Faults {
+            delay_every: Some(2),
+            delay: Duration::from_millis(50),
+            ..Faults::default()
+        },
+    )
+    .await;
+
+    sync_blockchain(
+        vec![peer.connect(&blockchain).await],
+        blockchain.clone(),
+        SyncProgress::new_shared(),
+        SyncConfig::default(),
+    )
+    .await?;
+
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    assert_eq!(
+        blockchain.block_store().get_last_block_hash(),
+        chain.last().unwrap().meta.hash.unwrap()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_with_tiny_reorder_buffer() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("tiny-reorder");
+    let peer = MockPeer::start(
+        chain,
+        Faults {
+            delay_every: Some(3),
+            delay: Duration::from_millis(20),
+            ..Faults::default()
+        },
+    )
+    .await;
+    let progress = SyncProgress::new_shared();
+
+    // A full buffer stops new requests, but must never deadlock the sync
+    sync_blockchain(
+        vec![peer.connect(&blockchain).await],
+        blockchain.clone(),
+        progress.clone(),
+        SyncConfig {
+            max_buffered_bytes: 1,
+            ..SyncConfig::default()
+        },
+    )
+    .await?;
+
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    assert_eq!(progress.read().await.buffered_blocks, 0);
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use std::{fs, net::SocketAddr, time::Duration};
+
+use log::LevelFilter;
+use snap_coin::full_node::node_state::NodeState;
+use tokio::{net::TcpListener, sync::watch, time::sleep};
+
+use crate::{
+    address_book::{ADDRESS_BOOK_FILE, AddressBook, PeerEntry, PeerSource},
+    reload::RuntimeSettings,
+    seeds::{IpPreference, connect_peers, resolve_seeds, start_seed_resolver},
+    tests::mock_peer::{Faults, MockPeer, test_chain, tmp_blockchain, tmp_path},
+};
+
+fn addrs(addrs: &[&str]) -> Vec<SocketAddr> {
+    addrs.iter().map(|addr| addr.parse().unwrap()).collect()
+}
+
+#[test]
+fn test_ip_preference() {
+    let mixed = addrs(&["10.0.0.1:1", "[::1]:2", "10.0.0.2:3", "[::2]:4"]);
+    assert_eq!(IpPreference::Any.apply(mixed.clone()), mixed);
+    assert_eq!(
+        IpPreference::Ipv4.apply(mixed.clone()),
+        addrs(&["10.0.0.1:1", "10.0.0.2:3", "[::1]:2", "[::2]:4"])
+    );
+    assert_eq!(
+        IpPreference::Ipv6.apply(mixed.clone()),
+        addrs(&["[::1]:2", "[::2]:4", "10.0.0.1:1", "10.0.0.2:3"])
+    );
+    assert_eq!(
+        IpPreference::Ipv4Only.apply(mixed.clone()),
+        addrs(&["10.0.0.1:1", "10.0.0.2:3"])
+    );
+    assert_eq!(
+        IpPreference::Ipv6Only.apply(mixed),
+        addrs(&["[::1]:2", "[::2]:4"])
+    );
+
+    for name in ["any", "ipv4", "ipv6", "ipv4-only", "ipv6-only"] {
+        assert_eq!(name.parse::<IpPreference>().unwrap().to_string(), name);
+    }
+    assert!("ipv5".parse::<IpPreference>().is_err());
+}
+
+#[tokio::test]
+async fn test_resolve_every_seed_address() -> Result<(), anyhow::Error> {
+    let seeds = [
+        "127.0.0.1:8998",
+        "[::1]:8998",
+        "127.0.0.1:8998",
+        "10.0.0.1:8998",
+    ]
+    .map(|seed| seed.to_string());
+
+    // Duplicates are dropped, every other address is kept
+    let mut resolved = resolve_seeds(&seeds, IpPreference::Any).await?;
+    resolved.sort();
+    assert_eq!(
+        resolved,
+        addrs(&["10.0.0.1:8998", "127.0.0.1:8998", "[::1]:8998"])
+    );
+
+    let resolved = resolve_seeds(&seeds, IpPreference::Ipv6).await?;
+    assert_eq!(resolved[0], "[::1]:8998".parse()?);
+    assert_eq!(resolved.len(), 3);
+    assert_eq!(
+        resolve_seeds(&seeds, IpPreference::Ipv6Only).await?,
+        addrs(&["[::1]:8998"])
+    );
+
+    // Hosts that fail are skipped, unless none resolves
+    let partial = ["nope".to_string(), "127.0.0.1:8998".to_string()];
+    assert_eq!(
+        resolve_seeds(&partial, IpPreference::Any).await?,
+        addrs(&["127.0.0.1:8998"])
+    );
+    let e = resolve_seeds(&["nope".to_string()], IpPreference::Any)
+        .await
+        .unwrap_err();
+    assert!(e.to_string().contains("seed peer: nope"), "{e}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_seed_resolver_picks_up_new_addresses() -> Result<(), anyhow::Error> {
+    let peer = MockPeer::start(test_chain().await, Faults::default()).await;
+    let blockchain = tmp_blockchain("seed-resolver");
+    let node_state = NodeState::new_empty();
+
+    // The seed host resolved to nothing reachable at startup
+    let (sender, settings) = watch::channel(RuntimeSettings {
+        seed_hosts: vec![peer.address.to_string()],
+        ip_preference: IpPreference::Any,
+        seeds: vec![],
+        reserved_ips: vec![],
+        max_peers: None,
+        min_peers: 0,
+        log_level: LevelFilter::Info,
+    });
+    let resolver = start_seed_resolver(
+        Duration::from_secs(1),
+        sender,
+        blockchain,
+        node_state.clone(),
+        AddressBook::empty(&tmp_path("seed-resolver")).into_shared(),
+    );
+    sleep(Duration::from_secs(3)).await;
+    assert_eq!(settings.borrow().seeds, vec![peer.address]);
+    assert!(
+        node_state
+            .connected_peers
+            .read()
+            .await
+            .contains_key(&peer.address)
+    );
+    resolver.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connect_peers_records_the_source() -> Result<(), anyhow::Error> {
+    let peer = MockPeer::start(test_chain().await, Faults::default()).await;
+    // Nothing listens on the port of a dropped listener
+    let closed = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+    let node_path = tmp_path("connect-peers");
+    fs::create_dir_all(&node_path)?;
+    let address_book = AddressBook::empty(&node_path).into_shared();
+
+    let connected = connect_peers(
+        &[closed, peer.address],
+        PeerSource::Discovered,
+        &tmp_blockchain("connect-peers"),
+        &NodeState::new_empty(),
+        &address_book,
+    )
+    .await;
+    assert_eq!(connected, 1);
+
+    address_book.lock().await.save()?;
+    let entries: Vec<PeerEntry> =
+        serde_json::from_slice(&fs::read(node_path.join(ADDRESS_BOOK_FILE))?)?;
+    assert_eq!(entries.len(), 2);
+    for entry in entries {
+        assert_eq!(entry.source, PeerSource::Discovered);
+        let expected = if entry.address == peer.address {
+            (1, 0)
+        } else {
+            (0, 1)
+        };
+        assert_eq!((entry.successes, entry.failures), expected);
+    }
+    fs::remove_dir_all(&node_path)?;
+    Ok(())
+}
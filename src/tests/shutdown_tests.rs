@@ -0,0 +1,101 @@
+use std::{sync::Arc, time::Duration};
+
+use snap_coin::{
+    core::blockchain::Blockchain,
+    full_node::{connect_peer, node_state::NodeState, p2p_server::start_p2p_server},
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    time::sleep,
+};
+
+use crate::{
+    address_book::AddressBook,
+    mempool::{MEMPOOL_FILE, restore_mempool},
+    progress::SyncProgress,
+    shutdown::{NodeTasks, ShutdownReason, shutdown_node},
+    sync::sync_blockchain,
+    tests::mock_peer::{Faults, MockPeer, TEST_CHAIN_HEIGHT, fast_config, test_chain, tmp_path},
+};
+
+#[tokio::test]
+async fn test_shutdown_stops_a_syncing_node() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let node_path = tmp_path("shutdown");
+    let blockchain = Arc::new(Blockchain::new(node_path.to_str().unwrap()));
+    let node_state = NodeState::new_empty();
+
+    // A peer that stops serving blocks halfway, so the sync is still running when the shutdown starts
+    let peer = MockPeer::start(
+        chain,
+        Faults {
+            stall_after: Some(TEST_CHAIN_HEIGHT / 2),
+            ..Faults::default()
+        },
+    )
+    .await;
+    let handle = connect_peer(peer.address, &blockchain, &node_state).await?;
+
+    let port = TcpListener::bind("127.0.0.1:0").await?.local_addr()?.port();
+    let mut listeners = NodeTasks::default();
+    listeners.add(
+        "P2P server",
+        start_p2p_server(port, blockchain.clone(), node_state.clone()).await?,
+    );
+
+    let mut tasks = NodeTasks::default();
+    let sync = {
+        let blockchain = blockchain.clone();
+        tokio::spawn(async move {
+            let _ = sync_blockchain(
+                vec![handle],
+                blockchain,
+                SyncProgress::new_shared(),
+                fast_config(),
+            )
+            .await;
+        })
+    };
+    tasks.add("sync", sync);
+    sleep(Duration::from_secs(2)).await;
+
+    let address_book = AddressBook::empty(&node_path).into_shared();
+    shutdown_node(
+        ShutdownReason::Quit,
+        listeners,
+        tasks,
+        &blockchain,
+        &node_state,
+        &address_book,
+        &node_path,
+    )
+    .await?;
+
+    let height = blockchain.block_store().get_height();
+    assert!(
+        height < TEST_CHAIN_HEIGHT,
+        "The sync should have been cancelled"
+    );
+    assert!(node_state.connected_peers.read().await.is_empty());
+    assert!(
+        TcpStream::connect(("127.0.0.1", port)).await.is_err(),
+        "The P2P server should no longer accept connections"
+    );
+    sleep(Duration::from_secs(1)).await;
+    assert_eq!(blockchain.block_store().get_height(), height);
+
+    // The mempool is saved, and restored once on the next start
+    assert!(node_path.join(MEMPOOL_FILE).exists());
+    assert_eq!(
+        restore_mempool(&node_path, &blockchain, &node_state).await?,
+        0
+    );
+    assert!(!node_path.join(MEMPOOL_FILE).exists());
+
+    // So is the peer that was connected at the end
+    assert_eq!(
+        AddressBook::load(&node_path)?.candidates(),
+        vec![peer.address]
+    );
+    Ok(())
+}
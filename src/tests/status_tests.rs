@@ -0,0 +1,51 @@
+use std::net::SocketAddr;
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+
+use crate::{progress::SyncProgress, status::start_status_server};
+
+/// Send `request` as is, and return the status line of the response
+async fn raw_request(port: u16, request: &[u8]) -> Result<String, anyhow::Error> {
+    let mut stream = TcpStream::connect(SocketAddr::from(([127, 0, 0, 1], port))).await?;
+    stream.write_all(request).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    Ok(response.lines().next().unwrap_or_default().to_string())
+}
+
+#[tokio::test]
+async fn test_status_server_bounds_requests() -> Result<(), anyhow::Error> {
+    let port = TcpListener::bind("127.0.0.1:0").await?.local_addr()?.port();
+    let (reload, _reload_receiver) = mpsc::channel(1);
+    let server = start_status_server(port, SyncProgress::new_shared(), reload).await?;
+
+    assert_eq!(
+        raw_request(port, b"GET /sync HTTP/1.1\r\nHost: localhost\r\n\r\n").await?,
+        "HTTP/1.1 200 OK"
+    );
+    assert_eq!(
+        raw_request(port, b"\r\n\r\n").await?,
+        "HTTP/1.1 400 Bad Request"
+    );
+
+    // Neither an endless request line nor endless headers are buffered
+    let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(10_000));
+    assert_eq!(
+        raw_request(port, long_line.as_bytes()).await?,
+        "HTTP/1.1 431 Request Header Fields Too Large"
+    );
+    let many_headers = format!(
+        "GET /sync HTTP/1.1\r\n{}\r\n",
+        "X-Filler: 0123456789\r\n".repeat(500)
+    );
+    assert_eq!(
+        raw_request(port, many_headers.as_bytes()).await?,
+        "HTTP/1.1 431 Request Header Fields Too Large"
+    );
+    server.abort();
+    Ok(())
+}
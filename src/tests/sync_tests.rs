@@ -0,0 +1,554 @@
+use std::time::{Duration, Instant};
+
+use snap_coin::{
+    build_block,
+    crypto::keys::Private,
+    full_node::{SharedBlockchain, connect_peer, node_state::NodeState},
+    node::{
+        message::{Command, Message},
+        peer::PeerHandle,
+    },
+};
+use tokio::{
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
+
+use crate::{
+    progress::SyncProgress,
+    sync::{
+        SyncConfig, rank_peers, run_exclusive_sync, select_sync_peers, start_catch_up_sync,
+        sync_blockchain,
+    },
+    tests::mock_peer::{
+        Faults, MockPeer, TEST_CHAIN_HEIGHT, fast_config, test_chain, tmp_blockchain,
+    },
+};
+
+#[tokio::test]
+async fn test_sync_from_healthy_peer() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("healthy");
+    let peer = MockPeer::start(chain.clone(), Faults::default()).await;
+    let progress = SyncProgress::new_shared();
+
+    sync_blockchain(
+        vec![peer.connect(&blockchain).await],
+        blockchain.clone(),
+        progress.clone(),
+        fast_config(),
+    )
+    .await?;
+
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    assert_eq!(
+        blockchain.block_store().get_last_block_hash(),
+        chain.last().unwrap().meta.hash.unwrap()
+    );
+
+    let progress = progress.read().await;
+    assert!(!progress.active, "Progress should be marked as finished");
+    assert_eq!(progress.target_height, TEST_CHAIN_HEIGHT);
+    assert_eq!(progress.applied_height, TEST_CHAIN_HEIGHT);
+    assert_eq!(progress.source_peer, Some(peer.address));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_fetches_hashes_in_windows() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("windows");
+    let peer = MockPeer::start(chain, Faults::default()).await;
+
+    sync_blockchain(
+        vec![peer.connect(&blockchain).await],
+        blockchain.clone(),
+        SyncProgress::new_shared(),
+        SyncConfig {
+            hash_window: 5,
+            ..fast_config()
+        },
+    )
+    .await?;
+
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    assert_eq!(
+        peer.hash_requests(),
+        TEST_CHAIN_HEIGHT.div_ceil(5),
+        "Hashes should be requested one window at a time"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_retries_dropped_responses() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("dropped");
+    let peer = MockPeer::start(
+        chain,
+        Faults {
+            drop_every: Some(3),
+            ..Faults::default()
+        },
+    )
+    .await;
+
+    sync_blockchain(
+        vec![peer.connect(&blockchain).await],
+        blockchain.clone(),
+        SyncProgress::new_shared(),
+        fast_config(),
+    )
+    .await?;
+
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    assert!(
+        peer.block_requests() > TEST_CHAIN_HEIGHT,
+        "Dropped blocks should have been requested again"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_resumes_after_corrupt_block() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("corrupt");
+    let peer = MockPeer::start(
+        chain,
+        Faults {
+            corrupt_every: Some(5),
+            ..Faults::default()
+        },
+    )
+    .await;
+
+    sync_blockchain(
+        vec![peer.connect(&blockchain).await],
+        blockchain.clone(),
+        SyncProgress::new_shared(),
+        fast_config(),
+    )
+    .await?;
+
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_fails_over_to_another_peer() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("failover");
+    // The broken peer claims to be ahead, so it is ranked first and asked first
+    let broken = MockPeer::start(
+        chain.clone(),
+        Faults {
+            empty_every: Some(1),
+            advertised_height: Some(TEST_CHAIN_HEIGHT + 1),
+            ..Faults::default()
+        },
+    )
+    .await;
+    let healthy = MockPeer::start(chain, Faults::default()).await;
+
+    sync_blockchain(
+        vec![
+            broken.connect(&blockchain).await,
+            healthy.connect(&blockchain).await,
+        ],
+        blockchain.clone(),
+        SyncProgress::new_shared(),
+        fast_config(),
+    )
+    .await?;
+
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    assert!(broken.block_requests() > 0, "Broken peer was never asked");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_gives_up_without_progress() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("give-up");
+    let broken = MockPeer::start(
+        chain,
+        Faults {
+            empty_every: Some(1),
+            ..Faults::default()
+        },
+    )
+    .await;
+
+    let progress = SyncProgress::new_shared();
+
+    let result = sync_blockchain(
+        vec![broken.connect(&blockchain).await],
+        blockchain.clone(),
+        progress.clone(),
+        fast_config(),
+    )
+    .await;
+
+    assert!(
+        result.is_err(),
+        "Sync should fail when no block can be fetched"
+    );
+    assert_eq!(blockchain.block_store().get_height(), 0);
+    assert!(
+        progress.read().await.last_error.is_some(),
+        "Failure should be recorded in the sync progress"
+    );
+
+    // A later sync starts without the old error
+    let healthy = MockPeer::start(test_chain().await, Faults::default()).await;
+    sync_blockchain(
+        vec![healthy.connect(&blockchain).await],
+        blockchain.clone(),
+        progress.clone(),
+        fast_config(),
+    )
+    .await?;
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    assert_eq!(progress.read().await.last_error, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_select_sync_peers_prefers_highest() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("select");
+    let empty = MockPeer::start(vec![], Faults::default()).await;
+    let short = MockPeer::start(chain[..TEST_CHAIN_HEIGHT / 2].to_vec(), Faults::default()).await;
+    let full = MockPeer::start(chain, Faults::default()).await;
+
+    let peers = vec![
+        empty.connect(&blockchain).await,
+        short.connect(&blockchain).await,
+        full.connect(&blockchain).await,
+    ];
+
+    let ranked = rank_peers(&peers, 0, &fast_config()).await;
+    assert_eq!(
+        ranked
+            .iter()
+            .map(|ranked| ranked.height)
+            .collect::<Vec<_>>(),
+        vec![TEST_CHAIN_HEIGHT, TEST_CHAIN_HEIGHT / 2, 0]
+    );
+
+    let selected = select_sync_peers(&peers, 0, &fast_config()).await;
+    assert_eq!(
+        selected.iter().map(|peer| peer.address).collect::<Vec<_>>(),
+        vec![full.address, short.address],
+        "Peers should be ordered by height, and peers that are not ahead left out"
+    );
+    assert!(
+        select_sync_peers(&peers, TEST_CHAIN_HEIGHT, &fast_config())
+            .await
+            .is_empty()
+    );
+    Ok(())
+}
+
+/// A local chain that shares the first `common` blocks of the test chain, followed by `fork` blocks of its own
+async fn forked_blockchain(name: &str, common: usize, fork: usize) -> SharedBlockchain {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain(name);
+    for block in &chain[..common] {
+        blockchain.add_block(block.clone(), true).unwrap();
+    }
+    let miner = Private::new_random().to_public();
+    for _ in 0..fork {
+        let mut block = build_block(&*blockchain, &vec![], miner).await.unwrap();
+        #[allow(deprecated)]
+        block.compute_pow().unwrap();
+        blockchain.add_block(block, false).unwrap();
+    }
+    blockchain
+}
+
+#[tokio::test]
+async fn test_sync_rolls_back_stale_fork() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = forked_blockchain("fork", TEST_CHAIN_HEIGHT / 2, 2).await;
+    let peer = MockPeer::start(chain.clone(), Faults::default()).await;
+    let progress = SyncProgress::new_shared();
+
+    sync_blockchain(
+        vec![peer.connect(&blockchain).await],
+        blockchain.clone(),
+        progress.clone(),
+        SyncConfig {
+            hash_window: 3,
+            ..fast_config()
+        },
+    )
+    .await?;
+
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    assert_eq!(
+        blockchain.block_store().get_last_block_hash(),
+        chain.last().unwrap().meta.hash.unwrap()
+    );
+    assert_eq!(progress.read().await.last_reorg_depth, Some(2));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_refuses_deep_reorg() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = forked_blockchain("deep-fork", TEST_CHAIN_HEIGHT / 2, 2).await;
+    let peer = MockPeer::start(chain, Faults::default()).await;
+
+    let result = sync_blockchain(
+        vec![peer.connect(&blockchain).await],
+        blockchain.clone(),
+        SyncProgress::new_shared(),
+        SyncConfig {
+            max_reorg_depth: 1,
+            ..fast_config()
+        },
+    )
+    .await;
+
+    assert!(result.is_err(), "Reorg deeper than the limit should fail");
+    assert_eq!(
+        blockchain.block_store().get_height(),
+        TEST_CHAIN_HEIGHT / 2 + 2,
+        "Local blocks should be kept when the reorg is refused"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_drops_stalled_peer() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("stalled");
+    // Ranked first, answers pings and hashes, but stops sending blocks
+    let stalled = MockPeer::start(
+        chain.clone(),
+        Faults {
+            stall_after: Some(3),
+            advertised_height: Some(TEST_CHAIN_HEIGHT + 1),
+            ..Faults::default()
+        },
+    )
+    .await;
+    let healthy = MockPeer::start(chain, Faults::default()).await;
+    let progress = SyncProgress::new_shared();
+
+    // Requests never time out by themselves, only the stall detector can move the sync on
+    let started_at = Instant::now();
+    sync_blockchain(
+        vec![
+            stalled.connect(&blockchain).await,
+            healthy.connect(&blockchain).await,
+        ],
+        blockchain.clone(),
+        progress.clone(),
+        SyncConfig {
+            request_timeout: Duration::from_secs(60),
+            stall_timeout: Duration::from_secs(1),
+            ..fast_config()
+        },
+    )
+    .await?;
+
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    assert!(started_at.elapsed() < Duration::from_secs(30));
+    assert!(
+        progress
+            .read()
+            .await
+            .last_error
+            .as_ref()
+            .is_some_and(|error| error.contains("stalled")),
+        "Stall should be recorded in the sync progress"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_drops_fallback_peer_that_stalls() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("stalled-fallback");
+    // The first blocks go to the broken peer, and fail over to the stalled one, which then holds up the sync
+    let broken = MockPeer::start(
+        chain.clone(),
+        Faults {
+            empty_every: Some(1),
+            advertised_height: Some(TEST_CHAIN_HEIGHT + 2),
+            ..Faults::default()
+        },
+    )
+    .await;
+    let stalled = MockPeer::start(
+        chain.clone(),
+        Faults {
+            stall_after: Some(0),
+            advertised_height: Some(TEST_CHAIN_HEIGHT + 1),
+            ..Faults::default()
+        },
+    )
+    .await;
+    let healthy = MockPeer::start(chain, Faults::default()).await;
+    let (broken, stalled) = (
+        broken.connect(&blockchain).await,
+        stalled.connect(&blockchain).await,
+    );
+
+    sync_blockchain(
+        vec![
+            broken.clone(),
+            stalled.clone(),
+            healthy.connect(&blockchain).await,
+        ],
+        blockchain.clone(),
+        SyncProgress::new_shared(),
+        SyncConfig {
+            request_timeout: Duration::from_secs(60),
+            stall_timeout: Duration::from_secs(1),
+            ..fast_config()
+        },
+    )
+    .await?;
+
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    let is_connected = async |peer: &PeerHandle| {
+        timeout(
+            Duration::from_secs(5),
+            peer.request(Message::new(Command::Ping { height: 0 })),
+        )
+        .await
+        .is_ok_and(|response| response.is_ok())
+    };
+    assert!(
+        !is_connected(&stalled).await,
+        "Stalled fallback peer should be dropped"
+    );
+    assert!(
+        is_connected(&broken).await,
+        "Peer that already failed over should not be blamed for the stall"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_fails_when_only_peer_stalls() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("only-stalled");
+    let stalled = MockPeer::start(
+        chain,
+        Faults {
+            stall_after: Some(0),
+            ..Faults::default()
+        },
+    )
+    .await;
+
+    let result = sync_blockchain(
+        vec![stalled.connect(&blockchain).await],
+        blockchain.clone(),
+        SyncProgress::new_shared(),
+        SyncConfig {
+            request_timeout: Duration::from_secs(60),
+            stall_timeout: Duration::from_millis(300),
+            ..fast_config()
+        },
+    )
+    .await;
+
+    assert!(result.is_err(), "Sync should give up instead of hanging");
+    Ok(())
+}
+
+/// A node connected to a mock peer serving the first `height` blocks of the test chain, with the catch-up daemon
+/// checking every 200ms and a threshold of 2 blocks
+async fn catch_up_node(name: &str, height: usize) -> (SharedBlockchain, MockPeer, JoinHandle<()>) {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain(name);
+    let node_state = NodeState::new_empty();
+    let peer = MockPeer::start(chain[..height].to_vec(), Faults::default()).await;
+    connect_peer(peer.address, &blockchain, &node_state)
+        .await
+        .unwrap();
+    let catch_up = start_catch_up_sync(
+        Duration::from_millis(200),
+        blockchain.clone(),
+        node_state,
+        SyncProgress::new_shared(),
+        2,
+        fast_config(),
+    );
+    (blockchain, peer, catch_up)
+}
+
+#[tokio::test]
+async fn test_catch_up_within_threshold_does_not_sync() -> Result<(), anyhow::Error> {
+    let (blockchain, peer, catch_up) = catch_up_node("catch-up-within", 2).await;
+    sleep(Duration::from_secs(2)).await;
+    assert_eq!(blockchain.block_store().get_height(), 0);
+    assert_eq!(peer.block_requests(), 0);
+    catch_up.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_catch_up_when_peer_grows_past_threshold() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let (blockchain, peer, catch_up) = catch_up_node("catch-up-grows", 2).await;
+    sleep(Duration::from_secs(1)).await;
+    assert_eq!(blockchain.block_store().get_height(), 0);
+
+    peer.extend(chain[2..].to_vec());
+    let started_at = Instant::now();
+    while blockchain.block_store().get_height() < TEST_CHAIN_HEIGHT {
+        assert!(
+            started_at.elapsed() < Duration::from_secs(30),
+            "The node did not catch up"
+        );
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(
+        blockchain.block_store().get_last_block_hash(),
+        chain.last().unwrap().meta.hash.unwrap()
+    );
+    catch_up.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exclusive_sync_skips_while_another_sync_runs() -> Result<(), anyhow::Error> {
+    let blockchain = tmp_blockchain("exclusive");
+    let node_state = NodeState::new_empty();
+    let peer = MockPeer::start(test_chain().await, Faults::default()).await;
+    let handle = connect_peer(peer.address, &blockchain, &node_state).await?;
+    let progress = SyncProgress::new_shared();
+
+    // Another sync is running, nothing is synced
+    *node_state.is_syncing.write().await = true;
+    let res = run_exclusive_sync(
+        vec![handle.clone()],
+        &blockchain,
+        &node_state,
+        &progress,
+        &fast_config(),
+    )
+    .await;
+    assert!(res.is_none());
+    assert_eq!(peer.block_requests(), 0);
+
+    *node_state.is_syncing.write().await = false;
+    run_exclusive_sync(
+        vec![handle],
+        &blockchain,
+        &node_state,
+        &progress,
+        &fast_config(),
+    )
+    .await
+    .expect("No other sync is running")?;
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    assert!(!*node_state.is_syncing.read().await);
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use snap_coin::core::blockchain::Blockchain;
+
+use crate::{
+    tests::mock_peer::{TEST_CHAIN_HEIGHT, test_chain, tmp_path},
+    verify::{reindex_chain, verify_chain},
+};
+
+/// A node directory holding the whole test chain
+async fn node_with_chain(name: &str) -> PathBuf {
+    let node_path = tmp_path(name);
+    let blockchain = Blockchain::new(node_path.join("blockchain").to_str().unwrap());
+    for block in test_chain().await {
+        blockchain.add_block(block, true).unwrap();
+    }
+    node_path
+}
+
+fn open(node_path: &Path) -> Blockchain {
+    Blockchain::new(node_path.join("blockchain").to_str().unwrap())
+}
+
+fn block_file(node_path: &Path, height: usize) -> PathBuf {
+    node_path.join(format!("blockchain/blockchain/blocks/{height}.dat"))
+}
+
+#[tokio::test]
+async fn test_verify_sound_chain() -> Result<(), anyhow::Error> {
+    let node_path = node_with_chain("verify-sound").await;
+
+    let report = verify_chain(&open(&node_path), &node_path.join("scratch"), false)?;
+
+    assert_eq!(report.height, TEST_CHAIN_HEIGHT);
+    assert_eq!(report.verified, TEST_CHAIN_HEIGHT);
+    assert!(report.first_invalid.is_none());
+    assert!(
+        !node_path.join("scratch").exists(),
+        "Scratch chain is cleaned up"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_verify_reports_first_bad_block() -> Result<(), anyhow::Error> {
+    let node_path = node_with_chain("verify-corrupt").await;
+    fs::write(block_file(&node_path, 5), b"garbage")?;
+    // Only the first of several bad blocks is reported
+    fs::copy(block_file(&node_path, 9), block_file(&node_path, 8))?;
+
+    let report = verify_chain(&open(&node_path), &node_path.join("scratch"), false)?;
+
+    assert_eq!(report.verified, 5);
+    assert_eq!(report.first_invalid.map(|(height, _)| height), Some(5));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_verify_detects_broken_linkage() -> Result<(), anyhow::Error> {
+    let node_path = node_with_chain("verify-linkage").await;
+    fs::copy(block_file(&node_path, 9), block_file(&node_path, 8))?;
+
+    let report = verify_chain(&open(&node_path), &node_path.join("scratch"), false)?;
+
+    assert_eq!(report.first_invalid.map(|(height, _)| height), Some(8));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reindex_rebuilds_valid_prefix() -> Result<(), anyhow::Error> {
+    let node_path = node_with_chain("reindex").await;
+    fs::write(block_file(&node_path, 7), b"garbage")?;
+
+    let report = reindex_chain(&node_path)?;
+
+    assert_eq!(report.verified, 7);
+    assert_eq!(report.first_invalid.map(|(height, _)| height), Some(7));
+    let blockchain = open(&node_path);
+    assert_eq!(blockchain.block_store().get_height(), 7);
+    let report = verify_chain(&blockchain, &node_path.join("scratch"), false)?;
+    assert!(report.first_invalid.is_none(), "Rebuilt store is sound");
+    assert!(!node_path.join("reindex.snaparc").exists());
+    Ok(())
+}
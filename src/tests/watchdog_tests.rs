@@ -0,0 +1,116 @@
+use std::{net::SocketAddr, time::Duration};
+
+use log::LevelFilter;
+use snap_coin::full_node::node_state::NodeState;
+use tokio::{net::TcpListener, time::sleep};
+
+use crate::{
+    address_book::AddressBook,
+    reload::RuntimeSettings,
+    seeds::IpPreference,
+    tests::mock_peer::{Faults, MockPeer, test_chain, tmp_blockchain, tmp_path},
+    watchdog::{Watchdog, backoff_delay},
+};
+
+fn settings(seeds: Vec<SocketAddr>, min_peers: usize) -> RuntimeSettings {
+    RuntimeSettings {
+        seed_hosts: vec![],
+        ip_preference: IpPreference::Any,
+        seeds,
+        reserved_ips: vec![],
+        max_peers: None,
+        min_peers,
+        log_level: LevelFilter::Info,
+    }
+}
+
+/// A local address nothing listens on
+async fn closed_address() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+#[test]
+fn test_backoff_delay() {
+    for (failures, max_secs) in [(1, 5), (2, 10), (3, 20), (7, 320), (8, 600), (100, 600)] {
+        let max = Duration::from_secs(max_secs);
+        for _ in 0..20 {
+            let delay = backoff_delay(failures);
+            assert!(
+                delay >= max / 2 && delay <= max,
+                "{failures} failure(s) waited {delay:?}"
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_watchdog_rotates_through_seeds() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let dead = closed_address().await;
+    let first = MockPeer::start(chain.clone(), Faults::default()).await;
+    let second = MockPeer::start(chain, Faults::default()).await;
+    let node_state = NodeState::new_empty();
+    let address_book = AddressBook::empty(&tmp_path("watchdog")).into_shared();
+    let mut watchdog = Watchdog::new(
+        tmp_blockchain("watchdog"),
+        node_state.clone(),
+        address_book.clone(),
+    );
+
+    // A seed that is down does not keep the node isolated
+    let reconnected = watchdog
+        .check(&settings(vec![dead, first.address], 1))
+        .await;
+    assert_eq!(
+        reconnected
+            .iter()
+            .map(|peer| peer.address)
+            .collect::<Vec<_>>(),
+        vec![first.address]
+    );
+    assert!(
+        watchdog
+            .check(&settings(vec![dead, first.address], 1))
+            .await
+            .is_empty()
+    );
+
+    // Below the minimum, the other seed is added. The dead one is backed off, not tried again right away
+    let reconnected = watchdog
+        .check(&settings(vec![dead, first.address, second.address], 2))
+        .await;
+    assert_eq!(reconnected.len(), 1);
+    assert_eq!(reconnected[0].address, second.address);
+
+    // A peer of the address book is reconnected, even once it is no seed anymore
+    let peer = node_state
+        .connected_peers
+        .read()
+        .await
+        .get(&first.address)
+        .cloned()
+        .unwrap();
+    let _ = peer.kill("Test".to_string()).await;
+    for _ in 0..50 {
+        if !node_state
+            .connected_peers
+            .read()
+            .await
+            .contains_key(&first.address)
+        {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    let reconnected = watchdog.check(&settings(vec![dead], 2)).await;
+    assert_eq!(reconnected.len(), 1);
+    assert_eq!(reconnected[0].address, first.address);
+
+    // Attempts are recorded, the address that only failed is tried last on the next start
+    assert_eq!(address_book.lock().await.candidates().last(), Some(&dead));
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use tokio::time::{sleep, timeout};
+
+use crate::{
+    progress::SyncProgress,
+    sync::{SyncConfig, sync_blockchain},
+    tests::mock_peer::{Faults, MockPeer, TEST_CHAIN_HEIGHT, test_chain, tmp_blockchain},
+    window::{DownloadWindow, INITIAL_WINDOW, MIN_WINDOW},
+};
+
+#[tokio::test]
+async fn test_window_grows_with_throughput() {
+    let window = DownloadWindow::new(1000);
+
+    // A full window answered within one round trip means the peer can take more
+    let slots = futures::future::join_all((0..INITIAL_WINDOW).map(|_| window.acquire())).await;
+    sleep(Duration::from_millis(20)).await;
+    for slot in slots {
+        slot.finish(true);
+    }
+
+    assert!(
+        window.limit() > INITIAL_WINDOW,
+        "Window should grow, is {}",
+        window.limit()
+    );
+    assert!(window.stats().0.is_some());
+}
+
+#[tokio::test]
+async fn test_window_respects_upper_bound() {
+    let window = DownloadWindow::new(12);
+
+    for _ in 0..5 {
+        let slots = futures::future::join_all((0..window.limit()).map(|_| window.acquire())).await;
+        sleep(Duration::from_millis(10)).await;
+        for slot in slots {
+            slot.finish(true);
+        }
+    }
+
+    assert_eq!(window.limit(), 12);
+}
+
+#[tokio::test]
+async fn test_window_halves_on_failure() {
+    let window = DownloadWindow::new(100);
+
+    window.acquire().await.finish(false);
+    assert_eq!(window.limit(), INITIAL_WINDOW / 2);
+
+    for _ in 0..10 {
+        window.acquire().await.finish(false);
+    }
+    assert_eq!(
+        window.limit(),
+        MIN_WINDOW,
+        "Window never shrinks below minimum"
+    );
+}
+
+#[tokio::test]
+async fn test_window_blocks_when_full() {
+    let window = DownloadWindow::new(1);
+
+    let slot = window.acquire().await;
+    assert!(
+        timeout(Duration::from_millis(50), window.acquire())
+            .await
+            .is_err(),
+        "Second request should wait for a free slot"
+    );
+
+    // Dropping a slot frees it too, e.g. when a download is cancelled
+    drop(slot);
+    assert!(
+        timeout(Duration::from_millis(50), window.acquire())
+            .await
+            .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn test_sync_with_single_request_window() -> Result<(), anyhow::Error> {
+    let chain = test_chain().await;
+    let blockchain = tmp_blockchain("single-window");
+    let peer = MockPeer::start(chain, Faults::default()).await;
+
+    sync_blockchain(
+        vec![peer.connect(&blockchain).await],
+        blockchain.clone(),
+        SyncProgress::new_shared(),
+        SyncConfig {
+            max_window: 1,
+            ..SyncConfig::default()
+        },
+    )
+    .await?;
+
+    assert_eq!(blockchain.block_store().get_height(), TEST_CHAIN_HEIGHT);
+    Ok(())
+}
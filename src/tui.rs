@@ -5,7 +5,7 @@ use std::{
 };
 
 use crossterm::{
-  event::{self, Event, KeyCode},
+  event::{self, Event, KeyCode, KeyModifiers},
   execute,
   terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
@@ -15,11 +15,16 @@ use log::info;
 use ratatui::{
   layout::{Constraint, Direction, Layout},
   prelude::CrosstermBackend,
-  widgets::{Block, Borders, Paragraph},
+  widgets::{Block, Borders, Gauge, Paragraph},
   Terminal,
 };
 use snap_coin::full_node::{node_state::SharedNodeState, SharedBlockchain};
 
+use crate::{
+  progress::{SharedSyncProgress, SyncProgress},
+  shutdown::Signals,
+};
+
 /// Returns the latest log file path in `node_path/logs/`
 fn latest_log_file(node_path: &str) -> Option<PathBuf> {
   let logs_dir = format!("{}/logs", node_path);
@@ -45,6 +50,46 @@ fn latest_log_file(node_path: &str) -> Option<PathBuf> {
   entries.first().map(|e| e.path())
 }
 
+/// Formats a seconds count as a short human readable duration
+fn format_eta(secs: u64) -> String {
+  if secs >= 3600 {
+    format!("{}h{}m", secs / 3600, (secs % 3600) / 60)
+  } else if secs >= 60 {
+    format!("{}m{}s", secs / 60, secs % 60)
+  } else {
+    format!("{}s", secs)
+  }
+}
+
+/// Label shown inside the sync gauge
+fn sync_label(progress: &SyncProgress) -> String {
+  let mut label = if progress.active {
+    format!(
+      "{}/{} ({:.1}%) | {:.1} blk/s | ETA {}",
+      progress.applied_height,
+      progress.target_height,
+      progress.ratio() * 100.0,
+      progress.blocks_per_sec,
+      progress.eta_secs.map(format_eta).unwrap_or("-".to_string()),
+    )
+  } else {
+    format!("Idle at {}", progress.applied_height)
+  };
+  if let Some(peer) = progress.source_peer {
+    label.push_str(&format!(" | From: {}", peer));
+  }
+  if progress.buffered_blocks > 0 {
+    label.push_str(&format!(" | Buffered: {}", progress.buffered_blocks));
+  }
+  if let Some(depth) = progress.last_reorg_depth {
+    label.push_str(&format!(" | Last reorg: {} blocks", depth));
+  }
+  if let Some(error) = &progress.last_error {
+    label.push_str(&format!(" | Last error: {}", error));
+  }
+  label
+}
+
 #[derive(Copy, Clone, PartialEq)]
 enum Focus {
   Stats,
@@ -55,8 +100,10 @@ enum Focus {
 pub async fn run_tui(
   node_state: SharedNodeState,
   blockchain: SharedBlockchain,
+  sync_progress: SharedSyncProgress,
   node_port: u16,
   node_path: String,
+  signals: Signals,
 ) -> anyhow::Result<()> {
   enable_raw_mode()?;
   let mut stdout = std::io::stdout();
@@ -80,13 +127,19 @@ pub async fn run_tui(
   let mut last_mempool_size = 0usize;
 
   loop {
-    let (height, last_block, peers, syncing) = {
+    // SIGTERM, or SIGINT sent from outside the terminal
+    if signals.received().is_some() {
+      break;
+    }
+
+    let (height, last_block, peers, syncing, progress) = {
       let height = blockchain.block_store().get_height();
       let last_block = blockchain
         .block_store()
         .get_last_block_hash()
         .dump_base36();
       let syncing = *node_state.is_syncing.read().await;
+      let progress = sync_progress.read().await.clone();
 
       let peers = node_state
         .connected_peers
@@ -96,7 +149,7 @@ pub async fn run_tui(
         .map(|p| (p.address, p.is_client))
         .collect::<Vec<_>>();
 
-      (height, last_block, peers, syncing)
+      (height, last_block, peers, syncing, progress)
     };
 
     if last_log_read.elapsed() > Duration::from_millis(300) {
@@ -118,6 +171,7 @@ pub async fn run_tui(
       let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
+          Constraint::Length(3),
           Constraint::Length(3),
           Constraint::Length(3),
           Constraint::Min(1),
@@ -142,6 +196,14 @@ pub async fn run_tui(
         layout[0],
       );
 
+      f.render_widget(
+        Gauge::default()
+          .block(Block::default().title("SYNC").borders(Borders::ALL))
+          .ratio(if progress.active { progress.ratio() } else { 1.0 })
+          .label(sync_label(&progress)),
+        layout[1],
+      );
+
       let peers_title = if focus == Focus::Peers {
         "*PEERS*"
       } else {
@@ -163,7 +225,7 @@ pub async fn run_tui(
         Paragraph::new(peers_line)
           .scroll((0, peers_scroll_x))
           .block(Block::default().title(peers_title).borders(Borders::ALL)),
-        layout[1],
+        layout[2],
       );
 
       let logs_title = if focus == Focus::Logs {
@@ -176,61 +238,59 @@ pub async fn run_tui(
         Paragraph::new(cached_log.as_str())
           .scroll((logs_scroll_y, logs_scroll_x))
           .block(Block::default().title(logs_title).borders(Borders::ALL)),
-        layout[2],
+        layout[3],
       );
     })?;
 
-    if event::poll(Duration::from_millis(50))? {
-      if let Event::Key(key) = event::read()? {
-        match key.code {
-          KeyCode::Char('q') => break,
-
-          KeyCode::Tab => {
-            focus = match focus {
-              Focus::Stats => Focus::Peers,
-              Focus::Peers => Focus::Logs,
-              Focus::Logs => Focus::Stats,
-            };
-          }
+    if event::poll(Duration::from_millis(50))?
+      && let Event::Key(key) = event::read()?
+    {
+      match key.code {
+        KeyCode::Char('q') => break,
+        // Raw mode turns Ctrl+C into a key press instead of SIGINT
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+
+        KeyCode::Tab => {
+          focus = match focus {
+            Focus::Stats => Focus::Peers,
+            Focus::Peers => Focus::Logs,
+            Focus::Logs => Focus::Stats,
+          };
+        }
 
-          KeyCode::Up => {
-            if focus == Focus::Logs {
-              logs_scroll_y = logs_scroll_y.saturating_sub(1);
-              auto_scroll_logs = false;
-            }
-          }
+        KeyCode::Up if focus == Focus::Logs => {
+          logs_scroll_y = logs_scroll_y.saturating_sub(1);
+          auto_scroll_logs = false;
+        }
 
-          KeyCode::Down => {
-            if focus == Focus::Logs {
-              logs_scroll_y =
-                (logs_scroll_y + 1).min(cached_log.lines().count() as u16);
-            }
-          }
+        KeyCode::Down if focus == Focus::Logs => {
+          logs_scroll_y =
+            (logs_scroll_y + 1).min(cached_log.lines().count() as u16);
+        }
 
-          KeyCode::Left => match focus {
-            Focus::Stats => stats_scroll_x = stats_scroll_x.saturating_sub(1),
-            Focus::Peers => peers_scroll_x = peers_scroll_x.saturating_sub(1),
-            Focus::Logs => logs_scroll_x = logs_scroll_x.saturating_sub(1),
-          },
-
-          KeyCode::Right => match focus {
-            Focus::Stats => stats_scroll_x += 1,
-            Focus::Peers => peers_scroll_x += 1,
-            Focus::Logs => logs_scroll_x += 1,
-          },
-
-          KeyCode::Char('c') if focus == Focus::Logs => {
-            if let Some(latest) = latest_log_file(&node_path) {
-              let _ = fs::write(latest, "");
-              info!("Log cleared");
-              cached_log.clear();
-              logs_scroll_y = 0;
-              auto_scroll_logs = true;
-            }
+        KeyCode::Left => match focus {
+          Focus::Stats => stats_scroll_x = stats_scroll_x.saturating_sub(1),
+          Focus::Peers => peers_scroll_x = peers_scroll_x.saturating_sub(1),
+          Focus::Logs => logs_scroll_x = logs_scroll_x.saturating_sub(1),
+        },
+
+        KeyCode::Right => match focus {
+          Focus::Stats => stats_scroll_x += 1,
+          Focus::Peers => peers_scroll_x += 1,
+          Focus::Logs => logs_scroll_x += 1,
+        },
+
+        KeyCode::Char('c') if focus == Focus::Logs => {
+          if let Some(latest) = latest_log_file(&node_path) {
+            let _ = fs::write(latest, "");
+            info!("Log cleared");
+            cached_log.clear();
+            logs_scroll_y = 0;
+            auto_scroll_logs = true;
           }
-
-          _ => {}
         }
+
+        _ => {}
       }
     }
   }
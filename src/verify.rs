@@ -0,0 +1,267 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::Path,
+    time::Instant,
+};
+
+use anyhow::anyhow;
+use snap_coin::core::{block::Block, blockchain::Blockchain};
+
+use crate::{
+    archive::ArchiveReader,
+    cli::{OptionSpec, Options, parse_bool, parse_value},
+    export::export_blocks,
+    lock::NodePathLock,
+    network::{Network, node_path_network, pin_difficulty},
+};
+
+/// How many verified blocks between progress lines
+const PROGRESS_INTERVAL: usize = 1000;
+
+/// Outcome of an offline chain verification
+#[derive(Clone, Debug)]
+pub struct VerifyReport {
+    /// Amount of blocks in the store
+    pub height: usize,
+    /// Amount of blocks that passed, from genesis on
+    pub verified: usize,
+    /// First block that failed, and why
+    pub first_invalid: Option<(usize, String)>,
+}
+
+/// Open the blockchain of a node, without creating one where there is none
+fn open_blockchain(node_path: &Path) -> Result<Blockchain, anyhow::Error> {
+    let blockchain_path = node_path.join("blockchain");
+    if !blockchain_path.exists() {
+        return Err(anyhow!("No blockchain found at {}", node_path.display()));
+    }
+    Ok(Blockchain::new(
+        blockchain_path
+            .to_str()
+            .ok_or_else(|| anyhow!("Invalid node path"))?,
+    ))
+}
+
+/// Apply `block` on top of `target` with full validation, the same as a synced block
+fn replay_block(
+    target: &Blockchain,
+    height: usize,
+    block: Block,
+    fixed_difficulty: bool,
+) -> Result<(), String> {
+    if target.block_store().get_height() != height {
+        return Err(format!(
+            "Expected chain height {height}, got {}",
+            target.block_store().get_height()
+        ));
+    }
+    target.add_block(block, true).map_err(|e| e.to_string())?;
+    if fixed_difficulty {
+        pin_difficulty(target);
+    }
+    if (height + 1).is_multiple_of(PROGRESS_INTERVAL) {
+        eprintln!("Verified {} blocks", height + 1);
+    }
+    Ok(())
+}
+
+/// Re-validate every stored block from genesis (PoW, linkage, transactions), by replaying the chain into a scratch blockchain at `scratch_path`.
+/// Also checks that the block index agrees with the stored blocks. `fixed_difficulty` is set for chains of networks with a fixed difficulty
+pub fn verify_chain(
+    blockchain: &Blockchain,
+    scratch_path: &Path,
+    fixed_difficulty: bool,
+) -> Result<VerifyReport, anyhow::Error> {
+    let _ = fs::remove_dir_all(scratch_path);
+    let scratch = Blockchain::new(
+        scratch_path
+            .to_str()
+            .ok_or_else(|| anyhow!("Invalid scratch path"))?,
+    );
+
+    let height = blockchain.block_store().get_height();
+    let mut first_invalid = None;
+    for h in 0..height {
+        let Some(block) = blockchain.block_store().get_block_by_height(h) else {
+            first_invalid = Some((h, "Block is missing or unreadable".to_string()));
+            break;
+        };
+        if blockchain.block_store().get_block_hash_by_height(h) != block.meta.hash {
+            first_invalid = Some((h, "Block index does not match the stored block".to_string()));
+            break;
+        }
+        if let Err(e) = replay_block(&scratch, h, block, fixed_difficulty) {
+            first_invalid = Some((h, e));
+            break;
+        }
+    }
+
+    drop(scratch);
+    fs::remove_dir_all(scratch_path)?;
+
+    Ok(VerifyReport {
+        height,
+        verified: first_invalid.as_ref().map_or(height, |(h, _)| *h),
+        first_invalid,
+    })
+}
+
+/// Rebuild the derived state (block index, UTXOs, difficulty) of a node from its raw blocks.
+/// The readable blocks are saved to an archive next to the store, the store is wiped and rebuilt from the archive with full validation.
+/// Everything from the first invalid block on is dropped, to be synced again from peers
+pub fn reindex_chain(node_path: &Path) -> Result<VerifyReport, anyhow::Error> {
+    let fixed_difficulty = node_path_network(node_path).is_some_and(Network::fixed_difficulty);
+    let archive_path = node_path.join("reindex.snaparc");
+    let height = {
+        let blockchain = open_blockchain(node_path)?;
+        let height = blockchain.block_store().get_height();
+        let readable = (0..height)
+            .find(|h| blockchain.block_store().get_block_by_height(*h).is_none())
+            .unwrap_or(height);
+        export_blocks(
+            &blockchain,
+            0,
+            readable,
+            BufWriter::new(File::create(&archive_path)?),
+        )?;
+        height
+    };
+
+    eprintln!(
+        "Saved raw blocks to {}, rebuilding the store",
+        archive_path.display()
+    );
+    fs::remove_dir_all(node_path.join("blockchain"))?;
+    let blockchain = Blockchain::new(
+        node_path
+            .join("blockchain")
+            .to_str()
+            .ok_or_else(|| anyhow!("Invalid node path"))?,
+    );
+
+    let mut archive = ArchiveReader::open(BufReader::new(File::open(&archive_path)?))?;
+    let readable = archive.header().block_count;
+    let mut first_invalid = None;
+    for h in 0..readable {
+        let block = match archive.next_block() {
+            Ok(Some(block)) => block,
+            Ok(None) => break,
+            Err(e) => {
+                first_invalid = Some((h, e.to_string()));
+                break;
+            }
+        };
+        if let Err(e) = replay_block(&blockchain, h, block, fixed_difficulty) {
+            first_invalid = Some((h, e));
+            break;
+        }
+    }
+    if first_invalid.is_none() && readable < height {
+        first_invalid = Some((readable, "Block is missing or unreadable".to_string()));
+    }
+    fs::remove_file(&archive_path)?;
+
+    Ok(VerifyReport {
+        height,
+        verified: blockchain.block_store().get_height(),
+        first_invalid,
+    })
+}
+
+/// Options of `snap-coin-node verify`
+pub const VERIFY_OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        name: "network",
+        value: Some("name"),
+        help: "Network of the node, selects the default node path [default: mainnet]",
+    },
+    OptionSpec {
+        name: "node-path",
+        value: Some("path"),
+        help: "Directory of the node data [default: ./node-<network>]",
+    },
+    OptionSpec {
+        name: "reindex",
+        value: None,
+        help: "Rebuild the store from its raw blocks",
+    },
+];
+
+#[derive(Clone, Debug, Default)]
+pub struct VerifyOptions {
+    pub network: Network,
+    /// `None` for the default node path of `network`
+    pub node_path: Option<String>,
+    pub reindex: bool,
+}
+
+impl VerifyOptions {
+    pub fn node_path(&self) -> &str {
+        self.node_path
+            .as_deref()
+            .unwrap_or(self.network.default_node_path())
+    }
+}
+
+impl Options for VerifyOptions {
+    fn set(&mut self, name: &str, value: &str) -> Result<(), anyhow::Error> {
+        match name {
+            "network" => self.network = parse_value(name, value, "mainnet, testnet or regtest")?,
+            "node-path" => self.node_path = Some(value.to_string()),
+            "reindex" => self.reindex = parse_bool(name, value)?,
+            _ => return Err(anyhow!("Unknown option '--{name}'")),
+        }
+        Ok(())
+    }
+}
+
+/// Entry point of `snap-coin-node verify`, checks the store of a node without starting it. `--reindex` rebuilds the store from its raw blocks
+pub fn run_verify(options: VerifyOptions) -> Result<(), anyhow::Error> {
+    let node_path = Path::new(options.node_path());
+    let reindex = options.reindex;
+    if !node_path.join("blockchain").exists() {
+        return Err(anyhow!("No blockchain found at {}", node_path.display()));
+    }
+    let _lock = NodePathLock::acquire(node_path)?;
+
+    let started_at = Instant::now();
+    let report = if reindex {
+        reindex_chain(node_path)?
+    } else {
+        let blockchain = open_blockchain(node_path)?;
+        let network = node_path_network(node_path).unwrap_or(options.network);
+        verify_chain(
+            &blockchain,
+            &node_path.join("verify-scratch"),
+            network.fixed_difficulty(),
+        )?
+    };
+    eprintln!(
+        "Checked {} blocks in {:.1}s",
+        report.verified,
+        started_at.elapsed().as_secs_f64()
+    );
+
+    match (report.first_invalid, reindex) {
+        (None, false) => {
+            println!("Chain is sound, all {} blocks are valid", report.height);
+            Ok(())
+        }
+        (None, true) => {
+            println!("Reindexed all {} blocks", report.height);
+            Ok(())
+        }
+        (Some((height, reason)), false) => Err(anyhow!(
+            "First invalid block at height {height}: {reason}. Run with --reindex to rebuild the store up to it"
+        )),
+        (Some((height, reason)), true) => {
+            println!(
+                "Reindexed {} blocks. Block {height} is invalid ({reason}), it and the {} blocks after it were dropped and will be synced again",
+                report.verified,
+                report.height.saturating_sub(height + 1)
+            );
+            Ok(())
+        }
+    }
+}
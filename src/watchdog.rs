@@ -0,0 +1,233 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+use snap_coin::{
+    full_node::{SharedBlockchain, connect_peer, node_state::SharedNodeState},
+    node::peer::PeerHandle,
+};
+use tokio::{
+    sync::watch,
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
+
+use crate::{
+    address_book::{PeerSource, SharedAddressBook, unix_now},
+    progress::SharedSyncProgress,
+    reload::RuntimeSettings,
+    seeds::CONNECT_TIMEOUT,
+    sync::{SyncConfig, run_exclusive_sync, select_sync_peers},
+};
+
+/// How often the outgoing peer count is checked
+pub const WATCHDOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Default least amount of outgoing peers the watchdog keeps
+pub const DEFAULT_MIN_PEERS: usize = 4;
+
+/// Wait after the first failed attempt at an address, doubled with every further failure
+const BACKOFF_BASE: Duration = Duration::from_secs(5);
+
+/// Longest wait between two attempts at an address
+const BACKOFF_MAX: Duration = Duration::from_secs(600);
+
+/// Wait before trying an address again after `failures` failed attempts in a row. Exponential, with jitter so nodes
+/// that lost the same seed do not all retry at once
+pub fn backoff_delay(failures: u32) -> Duration {
+    let delay = BACKOFF_BASE
+        .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .min(BACKOFF_MAX);
+    // Half the delay, plus up to the other half
+    delay / 2 + delay.mul_f64(rand::random::<f64>()) / 2
+}
+
+/// Failed attempts at an address since it was last connected
+#[derive(Clone, Copy, Debug)]
+struct Backoff {
+    failures: u32,
+    last_attempt: Instant,
+    retry_at: Instant,
+}
+
+/// Keeps at least `min_peers` outgoing peers, by rotating through the seeds and the peers of the address book.
+/// Addresses that fail are retried with exponential backoff
+pub struct Watchdog {
+    blockchain: SharedBlockchain,
+    node_state: SharedNodeState,
+    address_book: SharedAddressBook,
+    backoff: HashMap<SocketAddr, Backoff>,
+}
+
+impl Watchdog {
+    pub fn new(
+        blockchain: SharedBlockchain,
+        node_state: SharedNodeState,
+        address_book: SharedAddressBook,
+    ) -> Watchdog {
+        Watchdog {
+            blockchain,
+            node_state,
+            address_book,
+            backoff: HashMap::new(),
+        }
+    }
+
+    /// Connect to candidates until there are `min_peers` outgoing peers again, or no candidate is due. Returns the
+    /// newly connected peers
+    pub async fn check(&mut self, settings: &RuntimeSettings) -> Vec<PeerHandle> {
+        let (connected, outbound): (Vec<SocketAddr>, Vec<SocketAddr>) = {
+            let peers = self.node_state.connected_peers.read().await;
+            (
+                peers.keys().copied().collect(),
+                peers
+                    .values()
+                    .filter(|peer| !peer.is_client)
+                    .map(|peer| peer.address)
+                    .collect(),
+            )
+        };
+        // Never aim above the peer limit, the surplus would be disconnected right away
+        let min_peers = settings.max_peers.map_or(settings.min_peers, |max_peers| {
+            settings.min_peers.min(max_peers)
+        });
+        if outbound.len() >= min_peers {
+            return vec![];
+        }
+
+        let now = Instant::now();
+        let known = self.address_book.lock().await.candidates();
+        let mut candidates = Vec::new();
+        for address in settings.seeds.iter().chain(&known) {
+            if connected.contains(address) || candidates.contains(address) {
+                continue;
+            }
+            if self
+                .backoff
+                .get(address)
+                .is_some_and(|backoff| backoff.retry_at > now)
+            {
+                continue;
+            }
+            candidates.push(*address);
+        }
+        // Rotate, addresses that were not tried for the longest come first
+        candidates.sort_by_key(|address| self.backoff.get(address).map(|b| b.last_attempt));
+        if candidates.is_empty() {
+            return vec![];
+        }
+        if outbound.is_empty() {
+            warn!(
+                "No outgoing peers, trying {} seed or known peer address(es)",
+                candidates.len()
+            );
+        }
+
+        let mut reconnected = vec![];
+        for address in candidates {
+            if outbound.len() + reconnected.len() >= min_peers {
+                break;
+            }
+            let source = if settings.seeds.contains(&address) {
+                PeerSource::Seed
+            } else {
+                PeerSource::Discovered
+            };
+            let error = match timeout(
+                CONNECT_TIMEOUT,
+                connect_peer(address, &self.blockchain, &self.node_state),
+            )
+            .await
+            {
+                Ok(Ok(peer)) => {
+                    info!("Reconnected to {address}");
+                    self.backoff.remove(&address);
+                    self.address_book
+                        .lock()
+                        .await
+                        .record_success(address, source, unix_now());
+                    reconnected.push(peer);
+                    continue;
+                }
+                Ok(Err(e)) => e.to_string(),
+                Err(_) => "timed out".to_string(),
+            };
+            self.address_book
+                .lock()
+                .await
+                .record_failure(address, source, unix_now());
+
+            let failures = self
+                .backoff
+                .get(&address)
+                .map_or(1, |backoff| backoff.failures + 1);
+            let delay = backoff_delay(failures);
+            self.backoff.insert(
+                address,
+                Backoff {
+                    failures,
+                    last_attempt: now,
+                    retry_at: now + delay,
+                },
+            );
+            warn!(
+                "Failed to connect to {address}, retrying in {}s: {error}",
+                delay.as_secs()
+            );
+        }
+        reconnected
+    }
+}
+
+/// Run the watchdog every `interval`. When it reconnects a node that had lost all of its outgoing peers, the chain is
+/// synced from the new peers, as blocks were probably missed in the meantime
+pub fn start_reconnect_watchdog(
+    interval: Duration,
+    mut watchdog: Watchdog,
+    settings: watch::Receiver<RuntimeSettings>,
+    sync_progress: SharedSyncProgress,
+    sync_config: SyncConfig,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            sleep(interval).await;
+            let isolated = !watchdog
+                .node_state
+                .connected_peers
+                .read()
+                .await
+                .values()
+                .any(|peer| !peer.is_client);
+            let current = settings.borrow().clone();
+            let reconnected = watchdog.check(&current).await;
+            if !isolated || reconnected.is_empty() {
+                continue;
+            }
+
+            let local_height = watchdog.blockchain.block_store().get_height();
+            let best_peers = select_sync_peers(&reconnected, local_height, &sync_config).await;
+            if best_peers.is_empty() {
+                continue;
+            }
+            // The catch-up sync may be running already
+            let Some(res) = run_exclusive_sync(
+                best_peers,
+                &watchdog.blockchain,
+                &watchdog.node_state,
+                &sync_progress,
+                &sync_config,
+            )
+            .await
+            else {
+                continue;
+            };
+            match res {
+                Ok(()) => info!("Re-sync after reconnecting finished"),
+                Err(e) => error!("Re-sync after reconnecting failed: {e}"),
+            }
+        }
+    })
+}
@@ -0,0 +1,128 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Notify;
+
+/// Requests a peer may have in flight before anything is known about it
+pub const INITIAL_WINDOW: usize = 10;
+
+/// Requests a peer may always have in flight, even after failures
+pub const MIN_WINDOW: usize = 2;
+
+/// Default upper bound for the requests in flight to one peer
+pub const DEFAULT_MAX_WINDOW: usize = 128;
+
+struct WindowState {
+    limit: usize,
+    in_flight: usize,
+    min_rtt: Option<Duration>,
+    /// Estimated blocks per second the peer delivers
+    rate: f64,
+}
+
+/// Adaptive limit on the block requests in flight to one peer.
+/// The limit follows the bandwidth-delay product of the peer (throughput times the lowest observed round-trip time), doubled so there is headroom to find out if the peer can deliver more.
+/// Failures halve it
+pub struct DownloadWindow {
+    state: Mutex<WindowState>,
+    freed: Notify,
+    max_window: usize,
+}
+
+impl DownloadWindow {
+    pub fn new(max_window: usize) -> DownloadWindow {
+        let max_window = max_window.max(1);
+        DownloadWindow {
+            state: Mutex::new(WindowState {
+                limit: INITIAL_WINDOW.clamp(MIN_WINDOW.min(max_window), max_window),
+                in_flight: 0,
+                min_rtt: None,
+                rate: 0.0,
+            }),
+            freed: Notify::new(),
+            max_window,
+        }
+    }
+
+    /// Current amount of requests allowed in flight
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Lowest observed round-trip time, and estimated blocks per second
+    pub fn stats(&self) -> (Option<Duration>, f64) {
+        let state = self.state.lock().unwrap();
+        (state.min_rtt, state.rate)
+    }
+
+    /// Wait for a free slot in the window
+    pub async fn acquire(&self) -> WindowSlot<'_> {
+        loop {
+            // Created before checking, so a slot freed in between is not missed
+            let freed = self.freed.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < state.limit {
+                    state.in_flight += 1;
+                    return WindowSlot {
+                        window: self,
+                        sent_at: Instant::now(),
+                        success: None,
+                    };
+                }
+            }
+            freed.await;
+        }
+    }
+
+    fn release(&self, success: Option<bool>, rtt: Duration) {
+        {
+            let mut state = self.state.lock().unwrap();
+            match success {
+                Some(true) => {
+                    let min_rtt = state.min_rtt.map_or(rtt, |min_rtt| min_rtt.min(rtt));
+                    state.min_rtt = Some(min_rtt);
+                    // Little's law, everything in flight was delivered within one round trip
+                    let sample = state.in_flight as f64 / rtt.as_secs_f64().max(1e-6);
+                    state.rate = if sample > state.rate {
+                        sample
+                    } else {
+                        state.rate * 0.9 + sample * 0.1
+                    };
+                    let target = (2.0 * state.rate * min_rtt.as_secs_f64()).ceil() as usize;
+                    state.limit = target.clamp(MIN_WINDOW.min(self.max_window), self.max_window);
+                }
+                Some(false) => {
+                    state.rate /= 2.0;
+                    state.limit = (state.limit / 2).max(MIN_WINDOW.min(self.max_window));
+                }
+                // Cancelled, says nothing about the peer
+                None => {}
+            }
+            state.in_flight -= 1;
+        }
+        self.freed.notify_waiters();
+    }
+}
+
+/// A request slot taken from a `DownloadWindow`, freed when dropped
+pub struct WindowSlot<'a> {
+    window: &'a DownloadWindow,
+    sent_at: Instant,
+    success: Option<bool>,
+}
+
+impl WindowSlot<'_> {
+    /// Free the slot, feeding the outcome of the request back into the window
+    pub fn finish(mut self, success: bool) {
+        self.success = Some(success);
+    }
+}
+
+impl Drop for WindowSlot<'_> {
+    fn drop(&mut self) {
+        self.window.release(self.success, self.sent_at.elapsed());
+    }
+}